@@ -7,22 +7,17 @@ pub trait ReadExtras {
     fn read_u8(&mut self) -> io::Result<u8>;
     fn read_u16(&mut self) -> io::Result<u16>;
     fn read_u32(&mut self) -> io::Result<u32>;
-    fn read_u64(&mut self) -> io::Result<u64>;
 
     fn read_i32(&mut self) -> io::Result<i32>;
-    fn read_i64(&mut self) -> io::Result<i64>;
 
     fn read_f32(&mut self) -> io::Result<f32>;
 
-    fn read_u16_be(&mut self) -> io::Result<u16>;
     fn read_u32_be(&mut self) -> io::Result<u32>;
     fn read_u64_be(&mut self) -> io::Result<u64>;
 
     fn read_i32_be(&mut self) -> io::Result<i32>;
     fn read_i64_be(&mut self) -> io::Result<i64>;
 
-    fn read_f32_be(&mut self) -> io::Result<f32>;
-
     fn read_var_u32(&mut self) -> io::Result<u32>;
     fn read_u8_vec(&mut self, size: usize) -> io::Result<Vec<u8>>;
     fn read_c_string(&mut self) -> io::Result<Vec<u8>>;
@@ -56,22 +51,17 @@ impl<R: Read> ReadExtras for R {
     }
     read_le_fn!(read_u16, read_u16, u16);
     read_le_fn!(read_u32, read_u32, u32);
-    read_le_fn!(read_u64, read_u64, u64);
 
     read_le_fn!(read_i32, read_i32, i32);
-    read_le_fn!(read_i64, read_i64, i64);
 
     read_le_fn!(read_f32, read_f32, f32);
 
-    read_be_fn!(read_u16_be, read_u16, u16);
     read_be_fn!(read_u32_be, read_u32, u32);
     read_be_fn!(read_u64_be, read_u64, u64);
 
     read_be_fn!(read_i32_be, read_i32, i32);
     read_be_fn!(read_i64_be, read_i64, i64);
 
-    read_be_fn!(read_f32_be, read_f32, f32);
-
     fn read_var_u32(&mut self) -> io::Result<u32> {
         let mut res = 0;
         for byte in 0..=4 {
@@ -108,6 +98,30 @@ impl<R: Read> ReadExtras for R {
     }
 }
 
+/// Reader that keeps track of how many bytes have been read through it.
+pub struct CountingReader<R: Read> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> CountingReader<R> {
+        CountingReader { inner, position: 0 }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
 pub struct BitReader<R: Read> {
     head: Option<(u8, u8)>,
     tail: R,
@@ -164,7 +178,7 @@ impl<R: Read> BitReader<R> {
         while count > 0 {
             self.ensure_head()?;
             let (byte, bits_remaining): &mut (u8, u8) = self.head.as_mut().unwrap();
-            let shift = u8::min(count as u8, *bits_remaining);
+            let shift = u8::min(count, *bits_remaining);
 
             let byte_part = *byte & bitmask(shift);
 
@@ -201,7 +215,51 @@ pub fn string_from_nilslice(s: &[u8]) -> String {
     String::from_utf8_lossy(&data).to_string()
 }
 
+/// Writes bits in the order `BitReader` reads them, for building test data.
+#[cfg(test)]
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+#[cfg(test)]
+impl BitWriter {
+    pub fn new() -> BitWriter {
+        Default::default()
+    }
+
+    pub fn write_bits(&mut self, value: u32, count: u8) -> &mut BitWriter {
+        for i in 0..count {
+            if self.bits == self.bytes.len() * 8 {
+                self.bytes.push(0);
+            }
+            if value & (1 << i) != 0 {
+                *self.bytes.last_mut().unwrap() |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+        self
+    }
+
+    pub fn write_bit(&mut self, bit: bool) -> &mut BitWriter {
+        self.write_bits(bit as u32, 1)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> &mut BitWriter {
+        for byte in bytes {
+            self.write_bits(*byte as u32, 8);
+        }
+        self
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::useless_asref, clippy::unusual_byte_groupings)]
 mod test {
     use crate::bitreader::*;
     use std::io::Read;
@@ -0,0 +1,75 @@
+/// A bomb site, resolved from where the bomb was planted.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum BombSite {
+    A,
+    B,
+}
+
+impl BombSite {
+    /// The site whose center is closest to the position.
+    pub fn nearest(position: [f32; 3], center_a: [f32; 3], center_b: [f32; 3]) -> BombSite {
+        let distance = |center: [f32; 3]| {
+            (0..3)
+                .map(|i| (position[i] - center[i]).powi(2))
+                .sum::<f32>()
+        };
+        if distance(center_a) <= distance(center_b) {
+            BombSite::A
+        } else {
+            BombSite::B
+        }
+    }
+}
+
+/// What happened with the bomb in one round.
+///
+/// Players are identified by the first user id they had, see
+/// [`Player::user_ids`](crate::Player::user_ids).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BombRound {
+    /// Round number, counting from 1.
+    pub round: i32,
+    /// `None` if the bomb was not planted, or the site could not be resolved.
+    pub site: Option<BombSite>,
+    pub planter: Option<i32>,
+    /// Seconds from the start of the round to the plant.
+    pub plant_time: Option<f32>,
+    pub defuser: Option<i32>,
+    /// Seconds from the start of the round to the defuse.
+    pub defuse_time: Option<f32>,
+    /// Whether the successful defuse was done with a kit.
+    pub defuse_kit: bool,
+    /// Defuses that were started but not finished.
+    pub aborted_defuses: i32,
+    pub exploded: bool,
+}
+
+impl BombRound {
+    pub fn new(round: i32) -> BombRound {
+        BombRound {
+            round,
+            ..BombRound::default()
+        }
+    }
+
+    pub fn planted(&self) -> bool {
+        self.planter.is_some()
+    }
+
+    pub fn defused(&self) -> bool {
+        self.defuser.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bomb::*;
+
+    #[test]
+    fn nearest_site() {
+        let a = [1000.0, 200.0, 0.0];
+        let b = [-1500.0, 300.0, 64.0];
+        assert_eq!(BombSite::A, BombSite::nearest([900.0, 0.0, 0.0], a, b));
+        assert_eq!(BombSite::B, BombSite::nearest([-700.0, 250.0, 64.0], a, b));
+    }
+}
@@ -3,54 +3,74 @@ use std::io::Read;
 
 use crate::bitreader::ReadExtras;
 use crate::csgo::netmessages_public;
+use crate::error::Result;
 
 #[derive(Clone, Debug)]
 pub enum Cmd {
+    SetConVar(netmessages_public::CnetMsgSetConVar),
+    ServerInfo(netmessages_public::CsvcMsgServerInfo),
     CreateStringTable(netmessages_public::CsvcMsgCreateStringTable),
     UpdateStringTable(netmessages_public::CsvcMsgUpdateStringTable),
     UserMessage(netmessages_public::CsvcMsgUserMessage),
     GameEvent(netmessages_public::CsvcMsgGameEvent),
+    PacketEntities(netmessages_public::CsvcMsgPacketEntities),
     GameEventList(netmessages_public::CsvcMsgGameEventList),
 }
 
 impl Cmd {
-    pub fn parse<R: Read>(r: &mut R) -> Option<Cmd> {
+    /// Parse the next interesting message, or `None` at the end of the packet.
+    pub fn parse<R: Read>(r: &mut R) -> Result<Option<Cmd>> {
         loop {
-            let cmd = r.read_var_u32();
-            if cmd.is_err() {
-                return None;
-            }
-            let size = r.read_var_u32().unwrap();
-            let data = r.read_u8_vec(size as usize).unwrap();
+            let cmd = match r.read_var_u32() {
+                Ok(cmd) => cmd,
+                Err(_) => return Ok(None),
+            };
+            let size = r.read_var_u32()?;
+            let data = r.read_u8_vec(size as usize)?;
 
             // See: protos/netmessages_public.proto::SVC_Messages
-            match cmd.unwrap() {
+            match cmd {
+                6 => {
+                    return Ok(Some(Cmd::SetConVar(
+                        netmessages_public::CnetMsgSetConVar::decode(&*data)?,
+                    )));
+                }
+                8 => {
+                    return Ok(Some(Cmd::ServerInfo(
+                        netmessages_public::CsvcMsgServerInfo::decode(&*data)?,
+                    )));
+                }
                 12 => {
-                    return Some(Cmd::CreateStringTable(
-                        netmessages_public::CsvcMsgCreateStringTable::decode(&*data).unwrap(),
-                    ));
+                    return Ok(Some(Cmd::CreateStringTable(
+                        netmessages_public::CsvcMsgCreateStringTable::decode(&*data)?,
+                    )));
                 }
                 13 => {
-                    return Some(Cmd::UpdateStringTable(
-                        netmessages_public::CsvcMsgUpdateStringTable::decode(&*data).unwrap(),
-                    ));
+                    return Ok(Some(Cmd::UpdateStringTable(
+                        netmessages_public::CsvcMsgUpdateStringTable::decode(&*data)?,
+                    )));
                 }
 
                 23 => {
-                    return Some(Cmd::UserMessage(
-                        netmessages_public::CsvcMsgUserMessage::decode(&*data).unwrap(),
-                    ));
+                    return Ok(Some(Cmd::UserMessage(
+                        netmessages_public::CsvcMsgUserMessage::decode(&*data)?,
+                    )));
                 }
                 25 => {
-                    return Some(Cmd::GameEvent(
-                        netmessages_public::CsvcMsgGameEvent::decode(&*data).unwrap(),
-                    ));
+                    return Ok(Some(Cmd::GameEvent(
+                        netmessages_public::CsvcMsgGameEvent::decode(&*data)?,
+                    )));
+                }
+                26 => {
+                    return Ok(Some(Cmd::PacketEntities(
+                        netmessages_public::CsvcMsgPacketEntities::decode(&*data)?,
+                    )));
                 }
 
                 30 => {
-                    return Some(Cmd::GameEventList(
-                        netmessages_public::CsvcMsgGameEventList::decode(&*data).unwrap(),
-                    ));
+                    return Ok(Some(Cmd::GameEventList(
+                        netmessages_public::CsvcMsgGameEventList::decode(&*data)?,
+                    )));
                 }
 
                 _other => {}
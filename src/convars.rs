@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use crate::csgo::netmessages_public::CnetMsgSetConVar;
+
+/// Server console variables from `net_SetConVar`, with the tick of every change.
+#[derive(Clone, Debug, Default)]
+pub struct ConVars {
+    values: HashMap<String, Vec<(i32, String)>>,
+}
+
+impl ConVars {
+    /// Record the convars of a `net_SetConVar` message, returning the ones set.
+    pub fn apply(&mut self, msg: &CnetMsgSetConVar, tick: i32) -> Vec<(String, String)> {
+        let cvars = msg.convars.iter().flat_map(|convars| &convars.cvars);
+        let mut changed = Vec::new();
+        for cvar in cvars {
+            if let Some(name) = &cvar.name {
+                let value = cvar.value.clone().unwrap_or_default();
+                self.set(name, &value, tick);
+                changed.push((name.clone(), value));
+            }
+        }
+        changed
+    }
+
+    pub fn set(&mut self, name: &str, value: &str, tick: i32) {
+        self.values
+            .entry(name.to_string())
+            .or_default()
+            .push((tick, value.to_string()));
+    }
+
+    /// Latest value of a convar.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .get(name)
+            .and_then(|values| values.last())
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Value a convar had at the given tick.
+    pub fn get_at(&self, name: &str, tick: i32) -> Option<&str> {
+        self.values
+            .get(name)?
+            .iter()
+            .rev()
+            .find(|(changed, _)| *changed <= tick)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Latest value parsed as an integer. Float values are truncated.
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        let value = self.get(name)?.trim();
+        value
+            .parse()
+            .ok()
+            .or_else(|| value.parse::<f32>().ok().map(|n| n as i32))
+    }
+
+    /// Latest value as a boolean, the way the engine reads it: any non-zero number.
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get_i32(name).map(|n| n != 0)
+    }
+
+    /// Names and latest values of all convars.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().filter_map(|(name, values)| {
+            values
+                .last()
+                .map(|(_, value)| (name.as_str(), value.as_str()))
+        })
+    }
+}
+
+/// Part of a match that is played without switching sides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Period {
+    FirstHalf,
+    SecondHalf,
+    /// `half` of overtime `number`, both counting from 1.
+    Overtime {
+        number: i32,
+        half: i32,
+    },
+}
+
+/// Match format, from the `mp_*` convars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchRules {
+    /// Rounds in regulation, `mp_maxrounds`.
+    pub max_rounds: i32,
+    pub overtime_enabled: bool,
+    /// Rounds in each overtime, `mp_overtime_maxrounds`.
+    pub overtime_max_rounds: i32,
+    /// Whether teams switch sides at halftime, `mp_halftime`.
+    pub halftime: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> MatchRules {
+        MatchRules {
+            max_rounds: 30,
+            overtime_enabled: false,
+            overtime_max_rounds: 6,
+            halftime: true,
+        }
+    }
+}
+
+impl MatchRules {
+    /// Rules from the current convars, with server defaults for the ones not set.
+    pub fn from_convars(convars: &ConVars) -> MatchRules {
+        let default = MatchRules::default();
+        MatchRules {
+            max_rounds: convars
+                .get_i32("mp_maxrounds")
+                .filter(|n| *n > 0)
+                .unwrap_or(default.max_rounds),
+            overtime_enabled: convars
+                .get_bool("mp_overtime_enable")
+                .unwrap_or(default.overtime_enabled),
+            overtime_max_rounds: convars
+                .get_i32("mp_overtime_maxrounds")
+                .filter(|n| *n > 0)
+                .unwrap_or(default.overtime_max_rounds),
+            halftime: convars.get_bool("mp_halftime").unwrap_or(default.halftime),
+        }
+    }
+
+    /// Rounds a team has to win to win the match in regulation.
+    pub fn rounds_to_win(&self) -> i32 {
+        self.max_rounds / 2 + 1
+    }
+
+    /// Period of the given round, counting from 1.
+    pub fn period(&self, round: i32) -> Period {
+        if round <= self.max_rounds / 2 {
+            Period::FirstHalf
+        } else if round <= self.max_rounds {
+            Period::SecondHalf
+        } else {
+            let overtime_round = round - self.max_rounds - 1;
+            Period::Overtime {
+                number: overtime_round / self.overtime_max_rounds + 1,
+                half: if overtime_round % self.overtime_max_rounds < self.overtime_max_rounds / 2 {
+                    1
+                } else {
+                    2
+                },
+            }
+        }
+    }
+
+    /// Whether sides are switched before the given round (counting from 1).
+    ///
+    /// Teams switch at halftime and halfway through each overtime, but keep
+    /// their sides going into an overtime.
+    pub fn swaps_sides_before(&self, round: i32) -> bool {
+        if !self.halftime {
+            return false;
+        }
+        if round <= self.max_rounds {
+            return round == self.max_rounds / 2 + 1;
+        }
+        (round - self.max_rounds - 1) % self.overtime_max_rounds == self.overtime_max_rounds / 2
+    }
+
+    /// Whether the teams are on the opposite sides from where they started
+    /// in the given round.
+    pub fn sides_swapped(&self, round: i32) -> bool {
+        (1..=round)
+            .filter(|round| self.swaps_sides_before(*round))
+            .count()
+            % 2
+            == 1
+    }
+
+    /// Whether the match is over with the given score.
+    pub fn is_match_over(&self, score: (i32, i32)) -> bool {
+        let (a, b) = score;
+        let leader = a.max(b);
+        if a + b <= self.max_rounds {
+            return leader >= self.rounds_to_win()
+                || (!self.overtime_enabled && a + b == self.max_rounds);
+        }
+        if !self.overtime_enabled {
+            return true;
+        }
+        // Each overtime starts tied, and is won by winning more than half of it
+        let half = self.overtime_max_rounds / 2;
+        let overtime = (a + b - self.max_rounds - 1) / self.overtime_max_rounds;
+        let tied_at = self.max_rounds / 2 + overtime * half;
+        leader > tied_at + half
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::convars::*;
+    use crate::csgo::netmessages_public::{c_msg_c_vars, CMsgCVars};
+
+    fn set_convars(cvars: &[(&str, &str)]) -> CnetMsgSetConVar {
+        CnetMsgSetConVar {
+            convars: Some(CMsgCVars {
+                cvars: cvars
+                    .iter()
+                    .map(|(name, value)| c_msg_c_vars::CVar {
+                        name: Some(name.to_string()),
+                        value: Some(value.to_string()),
+                        dictionary_name: None,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    #[test]
+    fn convars_over_time() {
+        let mut convars = ConVars::default();
+        let changed = convars.apply(
+            &set_convars(&[("mp_maxrounds", "30"), ("sv_cheats", "0")]),
+            10,
+        );
+        assert_eq!(2, changed.len());
+        convars.apply(&set_convars(&[("mp_maxrounds", "24")]), 500);
+
+        assert_eq!(Some("24"), convars.get("mp_maxrounds"));
+        assert_eq!(Some("30"), convars.get_at("mp_maxrounds", 499));
+        assert_eq!(Some("24"), convars.get_at("mp_maxrounds", 500));
+        assert_eq!(None, convars.get_at("mp_maxrounds", 9));
+        assert_eq!(Some(false), convars.get_bool("sv_cheats"));
+        assert_eq!(None, convars.get("mp_halftime"));
+        assert_eq!(2, convars.iter().count());
+    }
+
+    #[test]
+    fn rules() {
+        let mut convars = ConVars::default();
+        assert_eq!(MatchRules::default(), MatchRules::from_convars(&convars));
+
+        convars.apply(
+            &set_convars(&[
+                ("mp_maxrounds", "24"),
+                ("mp_overtime_enable", "1"),
+                ("mp_overtime_maxrounds", "6.000000"),
+                ("mp_halftime", "1"),
+            ]),
+            0,
+        );
+        let rules = MatchRules::from_convars(&convars);
+        assert_eq!(24, rules.max_rounds);
+        assert!(rules.overtime_enabled);
+        assert_eq!(6, rules.overtime_max_rounds);
+        assert_eq!(13, rules.rounds_to_win());
+        assert!(rules.swaps_sides_before(13));
+        assert!(!rules.swaps_sides_before(16));
+        assert!(rules.is_match_over((13, 9)));
+        assert!(!rules.is_match_over((12, 12)));
+
+        assert_eq!(Period::FirstHalf, rules.period(12));
+        assert_eq!(Period::SecondHalf, rules.period(13));
+        assert_eq!(Period::SecondHalf, rules.period(24));
+        assert_eq!(Period::Overtime { number: 1, half: 1 }, rules.period(25));
+        assert_eq!(Period::Overtime { number: 1, half: 2 }, rules.period(28));
+        assert_eq!(Period::Overtime { number: 2, half: 1 }, rules.period(31));
+
+        let short = MatchRules {
+            max_rounds: 16,
+            ..MatchRules::default()
+        };
+        assert!(short.is_match_over((9, 5)));
+        assert!(short.is_match_over((8, 8)));
+        assert!(!short.is_match_over((8, 7)));
+    }
+
+    #[test]
+    fn overtime() {
+        let rules = MatchRules {
+            overtime_enabled: true,
+            ..MatchRules::default()
+        };
+        assert!(!rules.is_match_over((15, 15)));
+        assert!(!rules.is_match_over((18, 17)));
+        assert!(!rules.is_match_over((18, 18)));
+        assert!(rules.is_match_over((19, 17)));
+        assert!(!rules.is_match_over((21, 20)));
+        assert!(rules.is_match_over((22, 20)));
+        assert!(rules.is_match_over((16, 14)));
+
+        let swaps = (1..=42)
+            .filter(|round| rules.swaps_sides_before(*round))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![16, 34, 40], swaps);
+        assert!(!rules.sides_swapped(15));
+        assert!(rules.sides_swapped(31));
+        assert!(!rules.sides_swapped(34));
+        assert!(!rules.sides_swapped(37));
+        assert!(rules.sides_swapped(40));
+
+        let no_overtime = MatchRules::default();
+        assert!(no_overtime.is_match_over((15, 15)));
+        assert!(!no_overtime.is_match_over((15, 14)));
+    }
+}
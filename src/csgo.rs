@@ -2,7 +2,7 @@
 pub mod steammessages {
     include!(concat!(env!("OUT_DIR"), "/csgo.buf.steammessages.rs"));
 }
-#[allow(clippy::upper_case_acronyms)]
+#[allow(clippy::upper_case_acronyms, clippy::len_without_is_empty)]
 pub mod cstrike15_gcmessages {
     include!(concat!(
         env!("OUT_DIR"),
@@ -0,0 +1,222 @@
+use prost::Message;
+use std::collections::HashMap;
+
+use crate::bitreader::ReadExtras;
+use crate::csgo::netmessages_public::{csvc_msg_send_table, CsvcMsgSendTable};
+use crate::error::{DemoError, Result};
+
+pub type SendTable = CsvcMsgSendTable;
+pub type SendProp = csvc_msg_send_table::SendpropT;
+
+// Send prop types, see SendPropType in the Source SDK.
+pub const DPT_INT: i32 = 0;
+pub const DPT_FLOAT: i32 = 1;
+pub const DPT_VECTOR: i32 = 2;
+pub const DPT_VECTOR_XY: i32 = 3;
+pub const DPT_STRING: i32 = 4;
+pub const DPT_ARRAY: i32 = 5;
+pub const DPT_DATA_TABLE: i32 = 6;
+pub const DPT_INT64: i32 = 7;
+
+// Send prop flags
+pub const SPROP_UNSIGNED: i32 = 1 << 0;
+pub const SPROP_COORD: i32 = 1 << 1;
+pub const SPROP_NOSCALE: i32 = 1 << 2;
+pub const SPROP_NORMAL: i32 = 1 << 5;
+pub const SPROP_EXCLUDE: i32 = 1 << 6;
+pub const SPROP_INSIDEARRAY: i32 = 1 << 8;
+pub const SPROP_COLLAPSIBLE: i32 = 1 << 11;
+pub const SPROP_COORD_MP: i32 = 1 << 12;
+pub const SPROP_COORD_MP_LOWPRECISION: i32 = 1 << 13;
+pub const SPROP_COORD_MP_INTEGRAL: i32 = 1 << 14;
+pub const SPROP_CELL_COORD: i32 = 1 << 15;
+pub const SPROP_CELL_COORD_LOWPRECISION: i32 = 1 << 16;
+pub const SPROP_CELL_COORD_INTEGRAL: i32 = 1 << 17;
+pub const SPROP_CHANGES_OFTEN: i32 = 1 << 18;
+pub const SPROP_VARINT: i32 = 1 << 19;
+
+/// A networked entity class, linking a class id to its send table.
+#[derive(Clone, Debug)]
+pub struct ServerClass {
+    pub id: i32,
+    pub name: String,
+    pub table_name: String,
+}
+
+/// Send tables and server classes from the `dem_datatables` frame.
+#[derive(Clone, Debug, Default)]
+pub struct DataTables {
+    tables: Vec<SendTable>,
+    table_ids: HashMap<String, usize>,
+    classes: Vec<ServerClass>,
+    class_ids: HashMap<String, usize>,
+}
+
+impl DataTables {
+    /// Parse the payload of a `dem_datatables` frame.
+    pub fn new(data: &[u8]) -> Result<DataTables> {
+        let mut reader = data;
+        let mut tables = Vec::new();
+        let mut table_ids = HashMap::new();
+
+        loop {
+            // Always svc_SendTable
+            let _cmd = reader.read_var_u32()?;
+            let size = reader.read_var_u32()?;
+            let table = SendTable::decode(&*reader.read_u8_vec(size as usize)?)?;
+            if table.is_end.unwrap_or(false) {
+                break;
+            }
+            let name = table
+                .net_table_name
+                .clone()
+                .ok_or(DemoError::MissingField("net_table_name"))?;
+            table_ids.insert(name, tables.len());
+            tables.push(table);
+        }
+
+        let class_count = reader.read_u16()?;
+        let mut classes = Vec::with_capacity(class_count as usize);
+        let mut class_ids = HashMap::new();
+        for _ in 0..class_count {
+            let id = reader.read_u16()? as i32;
+            let name = String::from_utf8_lossy(&reader.read_c_string()?).to_string();
+            let table_name = String::from_utf8_lossy(&reader.read_c_string()?).to_string();
+            class_ids.insert(name.clone(), classes.len());
+            classes.push(ServerClass {
+                id,
+                name,
+                table_name,
+            });
+        }
+
+        Ok(DataTables {
+            tables,
+            table_ids,
+            classes,
+            class_ids,
+        })
+    }
+
+    pub fn tables(&self) -> &[SendTable] {
+        &self.tables
+    }
+
+    pub fn table(&self, name: &str) -> Option<&SendTable> {
+        self.table_ids.get(name).map(|i| &self.tables[*i])
+    }
+
+    /// Props of a send table, in the order they were networked.
+    pub fn table_props(&self, name: &str) -> Option<&[SendProp]> {
+        self.table(name).map(|table| table.props.as_slice())
+    }
+
+    pub fn classes(&self) -> &[ServerClass] {
+        &self.classes
+    }
+
+    pub fn class(&self, id: i32) -> Option<&ServerClass> {
+        self.classes
+            .get(id as usize)
+            .filter(|class| class.id == id)
+            .or_else(|| self.classes.iter().find(|class| class.id == id))
+    }
+
+    pub fn class_by_name(&self, name: &str) -> Option<&ServerClass> {
+        self.class_ids.get(name).map(|i| &self.classes[*i])
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::datatables::*;
+
+    pub fn prop(var_name: &str, prop_type: i32, flags: i32, num_bits: i32) -> SendProp {
+        SendProp {
+            r#type: Some(prop_type),
+            var_name: Some(var_name.to_string()),
+            flags: Some(flags),
+            priority: Some(128),
+            num_bits: Some(num_bits),
+            ..Default::default()
+        }
+    }
+
+    pub fn table(name: &str, props: Vec<SendProp>) -> SendTable {
+        SendTable {
+            is_end: Some(false),
+            net_table_name: Some(name.to_string()),
+            needs_decoder: Some(false),
+            props,
+        }
+    }
+
+    /// Encode send tables and (id, class name, table name) triples the way
+    /// the `dem_datatables` frame does.
+    pub fn encode(tables: &[SendTable], classes: &[(u16, &str, &str)]) -> Vec<u8> {
+        let end = SendTable {
+            is_end: Some(true),
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        for table in tables.iter().chain(std::iter::once(&end)) {
+            data.push(9);
+            prost::encoding::encode_varint(table.encoded_len() as u64, &mut data);
+            table.encode(&mut data).unwrap();
+        }
+        data.extend_from_slice(&(classes.len() as u16).to_le_bytes());
+        for (id, name, table_name) in classes {
+            data.extend_from_slice(&id.to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+            data.extend_from_slice(table_name.as_bytes());
+            data.push(0);
+        }
+        data
+    }
+
+    #[test]
+    fn parse_data_tables() {
+        let data = encode(
+            &[
+                table("DT_BaseEntity", vec![prop("m_iTeamNum", 0, 0, 6)]),
+                table(
+                    "DT_CSPlayer",
+                    vec![prop("m_iHealth", 0, 1, 7), prop("m_ArmorValue", 0, 1, 8)],
+                ),
+            ],
+            &[
+                (0, "CBaseEntity", "DT_BaseEntity"),
+                (1, "CCSPlayer", "DT_CSPlayer"),
+            ],
+        );
+
+        let tables = DataTables::new(&data).unwrap();
+        assert_eq!(2, tables.tables().len());
+        assert_eq!(2, tables.classes().len());
+
+        let class = tables.class_by_name("CCSPlayer").unwrap();
+        assert_eq!(1, class.id);
+        assert_eq!("DT_CSPlayer", class.table_name);
+        assert_eq!("CBaseEntity", tables.class(0).unwrap().name);
+        assert!(tables.class_by_name("CWeaponAWP").is_none());
+
+        let props = tables.table_props("DT_CSPlayer").unwrap();
+        let names = props
+            .iter()
+            .map(|p| p.var_name.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["m_iHealth", "m_ArmorValue"], names);
+        assert!(tables.table_props("DT_Missing").is_none());
+    }
+
+    #[test]
+    fn truncated_data_tables() {
+        let data = encode(
+            &[table("DT_BaseEntity", vec![])],
+            &[(0, "CBaseEntity", "DT_BaseEntity")],
+        );
+        let err = DataTables::new(&data[..data.len() - 3]).unwrap_err();
+        assert!(matches!(err, DemoError::TruncatedFrame));
+    }
+}
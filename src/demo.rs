@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use log::{debug, trace};
+
+use crate::bomb::BombSite;
+use crate::cmd::Cmd;
+use crate::convars::{ConVars, MatchRules};
+use crate::datatables::DataTables;
+use crate::entities::{Entities, Entity};
+use crate::error::Result;
+use crate::game_event::{Event, EventContext};
+use crate::handler::DemoHandler;
+use crate::header::Header;
+use crate::packet::CmdType;
+use crate::playerinfo::PlayerInfo;
+use crate::propdecode::PropValue;
+use crate::reader::{DemoReader, Frame, FramePayload};
+use crate::round::Side;
+use crate::serverinfo::ServerInfo;
+use crate::stringtables::{
+    read_string_tables_snapshot, StringTableEntry, StringTableSnapshot, StringTables,
+};
+
+/// Parser state shared with [`DemoHandler`] callbacks.
+#[derive(Clone, Debug)]
+pub struct Demo {
+    header: Header,
+    tick: i32,
+    server_info: Option<ServerInfo>,
+    convars: ConVars,
+    data_tables: DataTables,
+    entities: Entities,
+    string_tables: StringTables,
+    events: EventContext,
+    players: HashMap<i32, PlayerInfo>,
+}
+
+impl Demo {
+    fn new(header: Header) -> Demo {
+        Demo {
+            header,
+            tick: 0,
+            server_info: None,
+            convars: ConVars::default(),
+            data_tables: DataTables::default(),
+            entities: Entities::default(),
+            string_tables: StringTables::default(),
+            events: EventContext::default(),
+            players: HashMap::new(),
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Tick of the frame being parsed.
+    pub fn tick(&self) -> i32 {
+        self.tick
+    }
+
+    /// Server info, once `svc_ServerInfo` has been received.
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server_info.as_ref()
+    }
+
+    /// Ticks per second, from the server info if available, otherwise
+    /// estimated from the header.
+    pub fn tickrate(&self) -> i32 {
+        self.server_info
+            .as_ref()
+            .and_then(|info| info.tickrate())
+            .unwrap_or_else(|| self.header.tickrate())
+    }
+
+    pub fn convars(&self) -> &ConVars {
+        &self.convars
+    }
+
+    /// Match rules from the current convars.
+    pub fn rules(&self) -> MatchRules {
+        MatchRules::from_convars(&self.convars)
+    }
+
+    pub fn data_tables(&self) -> &DataTables {
+        &self.data_tables
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    pub fn string_tables(&self) -> &StringTables {
+        &self.string_tables
+    }
+
+    /// Latest `userinfo` of a player by user id.
+    pub fn player_info(&self, user_id: i32) -> Option<&PlayerInfo> {
+        self.players.get(&user_id)
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = &PlayerInfo> {
+        self.players.values()
+    }
+
+    /// Entity of a player by user id.
+    pub fn player_entity(&self, user_id: i32) -> Option<&Entity> {
+        let info = self.players.get(&user_id)?;
+        self.entities.get(info.entity_id as i32 + 1)
+    }
+
+    /// Position of a player by user id.
+    pub fn player_position(&self, user_id: i32) -> Option<[f32; 3]> {
+        let entity = self.player_entity(user_id)?;
+        ["csnonlocaldata", "cslocaldata"].iter().find_map(|table| {
+            let xy = entity.prop(&format!("{}.m_vecOrigin", table))?;
+            let z = entity.prop(&format!("{}.m_vecOrigin[2]", table))?;
+            match (xy, z) {
+                (PropValue::VectorXY([x, y]), PropValue::Float(z)) => Some([*x, *y, *z]),
+                _ => None,
+            }
+        })
+    }
+
+    /// Money a player has now, by user id.
+    pub fn money(&self, user_id: i32) -> Option<i32> {
+        self.player_prop_i32(user_id, "m_iAccount")
+    }
+
+    /// Money a player had at the start of the round.
+    pub fn round_start_money(&self, user_id: i32) -> Option<i32> {
+        self.player_prop_i32(user_id, "m_iStartAccount")
+    }
+
+    /// Value of the equipment a player carries now.
+    pub fn equipment_value(&self, user_id: i32) -> Option<i32> {
+        self.player_prop_i32(user_id, "m_unCurrentEquipmentValue")
+    }
+
+    /// Money a player spent this round, from the player resource.
+    pub fn money_spent(&self, user_id: i32) -> Option<i32> {
+        let entity = self.player_entity(user_id)?;
+        let resource = self.entities.by_class("CCSPlayerResource").next()?;
+        resource
+            .prop(&format!("m_iCashSpentThisRound.{:03}", entity.id))?
+            .as_i32()
+    }
+
+    fn player_prop_i32(&self, user_id: i32, name: &str) -> Option<i32> {
+        self.player_entity(user_id)?.prop(name)?.as_i32()
+    }
+
+    /// Clan name of the team on a side, if the server set one.
+    pub fn team_name(&self, side: Side) -> Option<&str> {
+        self.entities
+            .by_class("CCSTeam")
+            .find(|team| {
+                team.prop("m_iTeamNum").and_then(PropValue::as_i32) == Some(side.team_number())
+            })?
+            .prop("m_szClanTeamname")?
+            .as_str()
+    }
+
+    /// Bomb site closest to a player, eg. the one they are planting at.
+    pub fn bomb_site(&self, user_id: i32) -> Option<BombSite> {
+        let position = self.player_position(user_id)?;
+        let resource = self.entities.by_class("CCSPlayerResource").next()?;
+        let center = |name| resource.prop(name).and_then(PropValue::as_vector);
+        Some(BombSite::nearest(
+            position,
+            center("m_bombsiteCenterA")?,
+            center("m_bombsiteCenterB")?,
+        ))
+    }
+
+    fn handle_command<H: DemoHandler>(&mut self, cmd: Cmd, handler: &mut H) -> Result<()> {
+        match cmd {
+            Cmd::SetConVar(msg) => {
+                for (name, value) in self.convars.apply(&msg, self.tick) {
+                    trace!("ConVar {} = {}", name, value);
+                    handler.on_convar(self, &name, &value);
+                }
+            }
+            Cmd::ServerInfo(msg) => {
+                self.server_info = Some(ServerInfo::new(&msg));
+                let info = self.server_info.as_ref().unwrap();
+                debug!("Server info: {:?}", info);
+                handler.on_server_info(self, info);
+            }
+            Cmd::CreateStringTable(msg) => {
+                let (table, changes) = self.string_tables.create(msg)?;
+                let name = table.name.clone();
+                self.handle_string_table_changes(&name, changes, handler)?;
+            }
+            Cmd::UpdateStringTable(msg) => {
+                let (table, changes) = self.string_tables.update(msg)?;
+                let name = table.name.clone();
+                self.handle_string_table_changes(&name, changes, handler)?;
+            }
+            Cmd::PacketEntities(msg) => {
+                self.entities.handle_packet_entities(&msg)?;
+            }
+            Cmd::UserMessage(msg) => {
+                handler.on_user_message(self, &msg);
+            }
+            Cmd::GameEvent(msg) => {
+                let event = self.events.parse_game_event(msg, self.tick);
+                handler.on_game_event(self, &event);
+                handler.on_event(self, &Event::from_game_event(&event));
+            }
+            Cmd::GameEventList(event_list) => {
+                self.events = EventContext::new(event_list);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_string_table_changes<H: DemoHandler>(
+        &mut self,
+        table: &str,
+        changes: Vec<(i64, StringTableEntry)>,
+        handler: &mut H,
+    ) -> Result<()> {
+        match table {
+            "userinfo" => {
+                for (i, entry) in &changes {
+                    if let Some(userdata) = entry.userdata.as_ref().filter(|data| !data.is_empty())
+                    {
+                        let info = PlayerInfo::new(*i, userdata)?;
+                        self.update_player_info(info, handler);
+                    }
+                }
+            }
+            "instancebaseline" => {
+                for (_, entry) in &changes {
+                    if let (Ok(class_id), Some(userdata)) = (entry.string.parse(), &entry.userdata)
+                    {
+                        self.entities.set_baseline(class_id, userdata.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        handler.on_string_table_update(self, table, &changes);
+        Ok(())
+    }
+
+    fn update_player_info<H: DemoHandler>(&mut self, info: PlayerInfo, handler: &mut H) {
+        self.players.insert(info.user_id, info.clone());
+        handler.on_player_info(self, &info);
+    }
+
+    fn handle_string_tables_snapshot<H: DemoHandler>(
+        &mut self,
+        tables: Vec<StringTableSnapshot>,
+        handler: &mut H,
+    ) -> Result<()> {
+        self.string_tables.apply_snapshot(&tables);
+        for table in tables.into_iter().filter(|t| t.name == "userinfo") {
+            for (i, entry) in table.entries.into_iter().enumerate() {
+                if let Some(userdata) = entry.userdata.filter(|data| !data.is_empty()) {
+                    let info = PlayerInfo::new(i as i64, &userdata)?;
+                    self.update_player_info(info, handler);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_frame<H: DemoHandler>(&mut self, frame: Frame, handler: &mut H) -> Result<()> {
+        match frame.payload {
+            FramePayload::None => {}
+            FramePayload::Packet { data, .. } => {
+                let mut read = data.as_slice();
+                while let Some(cmd) = Cmd::parse(&mut read)? {
+                    self.handle_command(cmd, handler)?;
+                }
+            }
+            FramePayload::ConsoleCmd(cmd) => {
+                trace!("Console command: {}", cmd.command);
+            }
+            FramePayload::UserCmd(_) | FramePayload::CustomData(_) => {}
+            FramePayload::DataTables(data) => {
+                self.data_tables = DataTables::new(&data)?;
+                self.entities.update_classes(&self.data_tables)?;
+                debug!(
+                    "{} send tables, {} server classes",
+                    self.data_tables.tables().len(),
+                    self.data_tables.classes().len()
+                );
+            }
+            FramePayload::StringTables(data) => {
+                self.handle_string_tables_snapshot(read_string_tables_snapshot(&data)?, handler)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a whole demo, feeding everything in it to `handler`.
+///
+/// Returns the parser state as it was at the end of the demo.
+pub fn parse_demo<R: Read, H: DemoHandler>(reader: R, handler: &mut H) -> Result<Demo> {
+    let reader = DemoReader::new(reader)?;
+    handler.on_header(reader.header());
+
+    let mut demo = Demo::new(reader.header().clone());
+    let mut last_tick = None;
+
+    for frame in reader {
+        let frame = frame?;
+        let (offset, tick) = (frame.offset, frame.tick());
+        if frame.header.cmd_type == CmdType::Stop {
+            break;
+        }
+        demo.tick = tick;
+        if last_tick != Some(tick) {
+            last_tick = Some(tick);
+            handler.on_tick(&demo, tick);
+        }
+        demo.handle_frame(frame, handler)
+            .map_err(|e| e.at(offset, tick))?;
+        handler.on_frame_end(&demo);
+    }
+
+    Ok(demo)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use prost::Message;
+
+    use crate::csgo::netmessages_public;
+    use crate::demo::*;
+    use crate::game_event::GameEvent;
+
+    /// Demo with the send tables of a `dem_datatables` payload, for driving
+    /// handlers without a whole demo.
+    pub(crate) fn demo_with_data_tables(data: &[u8]) -> Demo {
+        let mut demo = Demo::new(Header::default());
+        demo.data_tables = DataTables::new(data).unwrap();
+        demo.entities.update_classes(&demo.data_tables).unwrap();
+        demo
+    }
+
+    /// Apply a message as if it was read from a packet.
+    pub(crate) fn apply<H: DemoHandler>(demo: &mut Demo, cmd: Cmd, handler: &mut H) {
+        demo.handle_command(cmd, handler).unwrap();
+    }
+
+    /// Add a player as if it was read from the `userinfo` string table.
+    pub(crate) fn add_player<H: DemoHandler>(demo: &mut Demo, info: PlayerInfo, handler: &mut H) {
+        demo.update_player_info(info, handler);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+    }
+
+    impl DemoHandler for Recorder {
+        fn on_header(&mut self, header: &Header) {
+            self.calls.push(format!("header {}", header.map));
+        }
+
+        fn on_tick(&mut self, _demo: &Demo, tick: i32) {
+            self.calls.push(format!("tick {}", tick));
+        }
+
+        fn on_server_info(&mut self, demo: &Demo, info: &ServerInfo) {
+            let stored = demo.server_info().map(|i| i.map_name.as_str());
+            self.calls
+                .push(format!("server info {} stored {:?}", info.map_name, stored));
+        }
+
+        fn on_game_event(&mut self, _demo: &Demo, event: &GameEvent) {
+            self.calls
+                .push(format!("game event {} at {}", event.name, event.tick));
+        }
+
+        fn on_event(&mut self, _demo: &Demo, event: &Event) {
+            self.calls.push(format!("event {:?}", event));
+        }
+
+        fn on_user_message(&mut self, _demo: &Demo, msg: &netmessages_public::CsvcMsgUserMessage) {
+            self.calls.push(format!("user message {:?}", msg.msg_type));
+        }
+
+        fn on_frame_end(&mut self, demo: &Demo) {
+            self.calls.push(format!("frame end {}", demo.tick()));
+        }
+    }
+
+    fn frame(data: &mut Vec<u8>, cmd_type: u8, tick: i32) {
+        data.push(cmd_type);
+        data.extend_from_slice(&tick.to_le_bytes());
+        data.push(0);
+    }
+
+    fn encode<M: Message>(msg: &M) -> Vec<u8> {
+        let mut data = Vec::new();
+        msg.encode(&mut data).unwrap();
+        data
+    }
+
+    fn packet(data: &mut Vec<u8>, tick: i32, messages: &[(u32, Vec<u8>)]) {
+        frame(data, 2, tick);
+        data.extend_from_slice(&[0; 2 * 76 + 8]);
+        let mut payload = Vec::new();
+        for (cmd, msg) in messages {
+            prost::encoding::encode_varint(*cmd as u64, &mut payload);
+            prost::encoding::encode_varint(msg.len() as u64, &mut payload);
+            payload.extend_from_slice(msg);
+        }
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&payload);
+    }
+
+    #[test]
+    fn handler_callbacks() {
+        let user_message = netmessages_public::CsvcMsgUserMessage {
+            msg_type: Some(6),
+            msg_data: Some(vec![]),
+            passthrough: None,
+        };
+        let game_event = netmessages_public::CsvcMsgGameEvent {
+            event_name: Some("custom_event".to_string()),
+            ..Default::default()
+        };
+        let server_info = netmessages_public::CsvcMsgServerInfo {
+            tick_interval: Some(0.0078125),
+            map_name: Some("de_vertigo".to_string()),
+            ..Default::default()
+        };
+
+        let mut data = include_bytes!("example_header").to_vec();
+        frame(&mut data, 3, 0);
+        packet(
+            &mut data,
+            5,
+            &[
+                (8, encode(&server_info)),
+                (23, encode(&user_message)),
+                (25, encode(&game_event)),
+            ],
+        );
+        frame(&mut data, 7, 5);
+
+        let mut recorder = Recorder::default();
+        let demo = parse_demo(data.as_slice(), &mut recorder).unwrap();
+        assert_eq!(5, demo.tick());
+        assert_eq!(
+            vec![
+                "header de_vertigo",
+                "tick 0",
+                "frame end 0",
+                "tick 5",
+                "server info de_vertigo stored Some(\"de_vertigo\")",
+                "user message Some(6)",
+                "game event custom_event at 5",
+                "event Other(\"custom_event\")",
+                "frame end 5",
+            ],
+            recorder.calls
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::round::Side;
+use crate::weapon::Weapon;
+
+/// Money of one player in one round.
+///
+/// The player is identified by the first user id they had, see
+/// [`Player::user_ids`](crate::Player::user_ids).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerEconomy {
+    pub player: i32,
+    /// `None` if the player had not joined a team.
+    pub side: Option<Side>,
+    /// Money at the start of the round.
+    pub start_money: i32,
+    pub money_spent: i32,
+    /// Items bought, in order, including those dropped for teammates.
+    pub items: Vec<Weapon>,
+    /// Value of the equipment carried when freeze time ended.
+    pub equipment_value: i32,
+    /// Money left at the end of the round.
+    pub remaining_money: i32,
+}
+
+/// Money of one team in one round, summed over its players.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TeamEconomy {
+    pub start_money: i32,
+    pub money_spent: i32,
+    pub items: i32,
+    pub equipment_value: i32,
+    pub remaining_money: i32,
+}
+
+/// Money of every player in one round.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EconomyRound {
+    /// Round number, counting from 1.
+    pub round: i32,
+    /// One record per player, ordered by player.
+    pub players: Vec<PlayerEconomy>,
+}
+
+impl EconomyRound {
+    pub fn team(&self, side: Side) -> TeamEconomy {
+        let mut total = TeamEconomy::default();
+        for player in self.players.iter().filter(|p| p.side == Some(side)) {
+            total.start_money += player.start_money;
+            total.money_spent += player.money_spent;
+            total.items += player.items.len() as i32;
+            total.equipment_value += player.equipment_value;
+            total.remaining_money += player.remaining_money;
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::economy::*;
+
+    #[test]
+    fn team_totals() {
+        let player = |player, side, start_money, items: &[Weapon]| PlayerEconomy {
+            player,
+            side,
+            start_money,
+            money_spent: start_money / 2,
+            items: items.to_vec(),
+            ..PlayerEconomy::default()
+        };
+        let round = EconomyRound {
+            round: 1,
+            players: vec![
+                player(1, Some(Side::T), 800, &[Weapon::Tec9]),
+                player(2, Some(Side::T), 1000, &[Weapon::Kevlar, Weapon::Smoke]),
+                player(3, Some(Side::CT), 800, &[]),
+                player(4, None, 800, &[Weapon::Glock]),
+            ],
+        };
+        let t = round.team(Side::T);
+        assert_eq!((1800, 900, 3), (t.start_money, t.money_spent, t.items));
+        assert_eq!(400, round.team(Side::CT).money_spent);
+    }
+}
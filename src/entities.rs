@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::bitreader::BitReader;
+use crate::csgo::netmessages_public::CsvcMsgPacketEntities;
+use crate::datatables::*;
+use crate::error::{DemoError, Result};
+use crate::flatten::{flatten, FlattenedProp};
+use crate::propdecode::{decode_prop, PropValue};
+
+const NUM_NETWORKED_EHANDLE_SERIAL_NUMBER_BITS: u8 = 10;
+const ENTITY_SENTINEL: i32 = 9999;
+
+/// A networked entity class with its flattened props.
+#[derive(Clone, Debug)]
+pub struct EntityClass {
+    pub id: i32,
+    pub name: String,
+    props: Vec<FlattenedProp>,
+    prop_ids: HashMap<String, usize>,
+}
+
+impl EntityClass {
+    /// Names of all props of this class, in the order they are networked.
+    pub fn prop_names(&self) -> impl Iterator<Item = &str> {
+        self.props.iter().map(|p| p.name.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Entity {
+    pub id: i32,
+    pub serial: u32,
+    /// Whether the entity is currently in the recorder's PVS.
+    pub in_pvs: bool,
+    class: Arc<EntityClass>,
+    values: Vec<Option<PropValue>>,
+}
+
+impl Entity {
+    pub fn class(&self) -> &EntityClass {
+        &self.class
+    }
+
+    pub fn class_name(&self) -> &str {
+        &self.class.name
+    }
+
+    /// Current value of a prop, eg. `m_iHealth` or `m_iAmmo.001`.
+    pub fn prop(&self, name: &str) -> Option<&PropValue> {
+        self.class
+            .prop_ids
+            .get(name)
+            .and_then(|i| self.values[*i].as_ref())
+    }
+
+    /// All props that have been networked for this entity.
+    pub fn props(&self) -> impl Iterator<Item = (&str, &PropValue)> {
+        self.class
+            .props
+            .iter()
+            .zip(self.values.iter())
+            .filter_map(|(prop, value)| value.as_ref().map(|v| (prop.name.as_str(), v)))
+    }
+}
+
+fn read_field_index<R: Read>(
+    reader: &mut BitReader<R>,
+    last_index: i32,
+    new_way: bool,
+) -> Result<Option<i32>> {
+    if new_way && reader.read_bit()? {
+        return Ok(Some(last_index + 1));
+    }
+    let index = if new_way && reader.read_bit()? {
+        reader.read_bits_u32(3)?
+    } else {
+        let index = reader.read_bits_u32(7)?;
+        match index & (32 | 64) {
+            32 => (index & !96) | (reader.read_bits_u32(2)? << 5),
+            64 => (index & !96) | (reader.read_bits_u32(4)? << 5),
+            96 => (index & !96) | (reader.read_bits_u32(7)? << 5),
+            _ => index,
+        }
+    };
+    if index == 0xFFF {
+        return Ok(None);
+    }
+    Ok(Some(last_index + 1 + index as i32))
+}
+
+fn read_ubit_var<R: Read>(reader: &mut BitReader<R>) -> Result<u32> {
+    let value = reader.read_bits_u32(6)?;
+    Ok(match value & (16 | 32) {
+        16 => (value & 15) | (reader.read_bits_u32(4)? << 4),
+        32 => (value & 15) | (reader.read_bits_u32(8)? << 4),
+        48 => (value & 15) | (reader.read_bits_u32(32 - 4)? << 4),
+        _ => value,
+    })
+}
+
+/// Read a list of changed props and apply them to `values`.
+fn read_props<R: Read>(
+    reader: &mut BitReader<R>,
+    class: &EntityClass,
+    values: &mut [Option<PropValue>],
+) -> Result<()> {
+    let new_way = reader.read_bit()?;
+    let mut indices = Vec::new();
+    let mut index = -1;
+    while let Some(next) = read_field_index(reader, index, new_way)? {
+        index = next;
+        indices.push(index as usize);
+    }
+
+    for i in indices {
+        let prop = class.props.get(i).ok_or_else(|| {
+            DemoError::EntityCorruption(format!("prop index {} out of bounds in {}", i, class.name))
+        })?;
+        values[i] = Some(decode_prop(
+            reader,
+            &prop.prop,
+            prop.array_element.as_ref(),
+        )?);
+    }
+    Ok(())
+}
+
+/// The live entity table, updated from `svc_PacketEntities` messages.
+#[derive(Clone, Debug, Default)]
+pub struct Entities {
+    classes: HashMap<i32, Arc<EntityClass>>,
+    class_bits: u8,
+    baselines: HashMap<i32, Vec<u8>>,
+    decoded_baselines: HashMap<i32, Vec<Option<PropValue>>>,
+    entities: HashMap<i32, Entity>,
+}
+
+impl Entities {
+    /// Flatten the props of every server class. Entities created before the
+    /// data tables changed are dropped, baselines are kept.
+    pub fn update_classes(&mut self, data_tables: &DataTables) -> Result<()> {
+        let classes = data_tables.classes();
+        self.classes = classes
+            .iter()
+            .map(|class| {
+                let props = flatten(data_tables, &class.table_name)?;
+                let prop_ids = props
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| (p.name.clone(), i))
+                    .collect();
+                let class = EntityClass {
+                    id: class.id,
+                    name: class.name.clone(),
+                    props,
+                    prop_ids,
+                };
+                Ok((class.id, Arc::new(class)))
+            })
+            .collect::<Result<_>>()?;
+        self.class_bits = (32 - (classes.len() as u32).leading_zeros()) as u8;
+        self.decoded_baselines.clear();
+        self.entities.clear();
+        Ok(())
+    }
+
+    /// Set the instance baseline of a class from the `instancebaseline` string table.
+    pub fn set_baseline(&mut self, class_id: i32, data: Vec<u8>) {
+        self.decoded_baselines.remove(&class_id);
+        self.baselines.insert(class_id, data);
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    pub fn by_class<'a>(&'a self, class_name: &'a str) -> impl Iterator<Item = &'a Entity> {
+        self.iter().filter(move |e| e.class_name() == class_name)
+    }
+
+    fn baseline(&mut self, class: &Arc<EntityClass>) -> Result<Vec<Option<PropValue>>> {
+        if let Some(values) = self.decoded_baselines.get(&class.id) {
+            return Ok(values.clone());
+        }
+        let mut values = vec![None; class.props.len()];
+        if let Some(data) = self.baselines.get(&class.id) {
+            let mut reader = BitReader::new(data.as_slice());
+            read_props(&mut reader, class, &mut values)?;
+        }
+        self.decoded_baselines.insert(class.id, values.clone());
+        Ok(values)
+    }
+
+    fn enter_pvs<R: Read>(&mut self, reader: &mut BitReader<R>, id: i32) -> Result<()> {
+        let class_id = reader.read_bits_u32(self.class_bits)? as i32;
+        let serial = reader.read_bits_u32(NUM_NETWORKED_EHANDLE_SERIAL_NUMBER_BITS)?;
+        let class = self.classes.get(&class_id).cloned().ok_or_else(|| {
+            DemoError::EntityCorruption(format!("unknown class {} for entity {}", class_id, id))
+        })?;
+
+        let mut values = self.baseline(&class)?;
+        read_props(reader, &class, &mut values)?;
+        self.entities.insert(
+            id,
+            Entity {
+                id,
+                serial,
+                in_pvs: true,
+                class,
+                values,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn handle_packet_entities(&mut self, msg: &CsvcMsgPacketEntities) -> Result<()> {
+        let data = msg
+            .entity_data
+            .as_ref()
+            .ok_or(DemoError::MissingField("entity_data"))?;
+        let mut reader = BitReader::new(data.as_slice());
+        let is_delta = msg.is_delta.unwrap_or(false);
+
+        if !is_delta {
+            self.entities.clear();
+        }
+
+        let mut id = -1;
+        for _ in 0..msg.updated_entries.unwrap_or(0) {
+            id += 1 + read_ubit_var(&mut reader)? as i32;
+            if id > ENTITY_SENTINEL {
+                break;
+            }
+
+            if !reader.read_bit()? {
+                if reader.read_bit()? {
+                    self.enter_pvs(&mut reader, id)?;
+                } else {
+                    let entity = self.entities.get_mut(&id).ok_or_else(|| {
+                        DemoError::EntityCorruption(format!("delta for unknown entity {}", id))
+                    })?;
+                    read_props(&mut reader, &entity.class, &mut entity.values)?;
+                }
+            } else if reader.read_bit()? {
+                self.entities.remove(&id);
+            } else if let Some(entity) = self.entities.get_mut(&id) {
+                entity.in_pvs = false;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitreader::BitWriter;
+    use crate::datatables::test::{encode, prop, table};
+    use crate::entities::*;
+
+    fn entities() -> Entities {
+        let data = encode(
+            &[table(
+                "DT_CSPlayer",
+                vec![
+                    prop("m_iHealth", DPT_INT, SPROP_UNSIGNED, 7),
+                    prop("m_szLastPlaceName", DPT_STRING, 0, 0),
+                ],
+            )],
+            &[(0, "CCSPlayer", "DT_CSPlayer")],
+        );
+        let mut entities = Entities::default();
+        entities
+            .update_classes(&DataTables::new(&data).unwrap())
+            .unwrap();
+        entities
+    }
+
+    fn write_end_of_props(w: &mut BitWriter) {
+        w.write_bit(false).write_bit(false);
+        w.write_bits(127, 7).write_bits(127, 7);
+    }
+
+    fn packet(updated_entries: i32, is_delta: bool, data: Vec<u8>) -> CsvcMsgPacketEntities {
+        CsvcMsgPacketEntities {
+            updated_entries: Some(updated_entries),
+            is_delta: Some(is_delta),
+            entity_data: Some(data),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn create_update_delete() {
+        let mut entities = entities();
+
+        // Entity 3 enters the PVS with both props set.
+        let mut w = BitWriter::new();
+        w.write_bits(3, 6).write_bit(false).write_bit(true);
+        w.write_bits(0, 1).write_bits(5, 10);
+        w.write_bit(true).write_bit(true).write_bit(true);
+        write_end_of_props(&mut w);
+        w.write_bits(100, 7);
+        w.write_bits(3, 9).write_bytes(b"Mid");
+        entities
+            .handle_packet_entities(&packet(1, false, w.finish()))
+            .unwrap();
+
+        let entity = entities.get(3).unwrap();
+        assert_eq!("CCSPlayer", entity.class_name());
+        assert_eq!(5, entity.serial);
+        assert_eq!(Some(100), entity.prop("m_iHealth").and_then(|p| p.as_i32()));
+        assert_eq!(
+            Some("Mid"),
+            entity.prop("m_szLastPlaceName").and_then(|p| p.as_str())
+        );
+
+        // Delta update of the health only.
+        let mut w = BitWriter::new();
+        w.write_bits(3, 6).write_bit(false).write_bit(false);
+        w.write_bit(true).write_bit(true);
+        write_end_of_props(&mut w);
+        w.write_bits(73, 7);
+        entities
+            .handle_packet_entities(&packet(1, true, w.finish()))
+            .unwrap();
+
+        let entity = entities.get(3).unwrap();
+        assert_eq!(Some(73), entity.prop("m_iHealth").and_then(|p| p.as_i32()));
+        assert_eq!(
+            Some("Mid"),
+            entity.prop("m_szLastPlaceName").and_then(|p| p.as_str())
+        );
+
+        // Leave the PVS, then get deleted.
+        let mut w = BitWriter::new();
+        w.write_bits(3, 6).write_bit(true).write_bit(false);
+        entities
+            .handle_packet_entities(&packet(1, true, w.finish()))
+            .unwrap();
+        assert!(!entities.get(3).unwrap().in_pvs);
+
+        let mut w = BitWriter::new();
+        w.write_bits(3, 6).write_bit(true).write_bit(true);
+        entities
+            .handle_packet_entities(&packet(1, true, w.finish()))
+            .unwrap();
+        assert!(entities.get(3).is_none());
+    }
+
+    #[test]
+    fn instance_baseline() {
+        let mut entities = entities();
+
+        let mut w = BitWriter::new();
+        w.write_bit(true).write_bit(true);
+        write_end_of_props(&mut w);
+        w.write_bits(100, 7);
+        entities.set_baseline(0, w.finish());
+
+        // Entity 1 enters the PVS with only the place name set.
+        let mut w = BitWriter::new();
+        w.write_bits(1, 6).write_bit(false).write_bit(true);
+        w.write_bits(0, 1).write_bits(0, 10);
+        w.write_bit(true)
+            .write_bit(false)
+            .write_bit(true)
+            .write_bits(1, 3);
+        write_end_of_props(&mut w);
+        w.write_bits(1, 9).write_bytes(b"A");
+        entities
+            .handle_packet_entities(&packet(1, false, w.finish()))
+            .unwrap();
+
+        let entity = entities.get(1).unwrap();
+        assert_eq!(Some(100), entity.prop("m_iHealth").and_then(|p| p.as_i32()));
+        assert_eq!(
+            Some("A"),
+            entity.prop("m_szLastPlaceName").and_then(|p| p.as_str())
+        );
+        assert_eq!(2, entity.props().count());
+    }
+
+    #[test]
+    fn delta_for_unknown_entity() {
+        let mut entities = entities();
+        let mut w = BitWriter::new();
+        w.write_bits(3, 6).write_bit(false).write_bit(false);
+        let err = entities
+            .handle_packet_entities(&packet(1, true, w.finish()))
+            .unwrap_err();
+        assert!(matches!(err, DemoError::EntityCorruption(_)));
+    }
+}
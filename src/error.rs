@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, DemoError>;
+
+#[derive(Debug)]
+pub enum DemoError {
+    /// The file does not start with `HL2DEMO`.
+    BadMagic(String),
+    /// The demo protocol version is not one we know how to parse.
+    UnsupportedProtocol(i32),
+    /// The demo ended in the middle of a frame.
+    TruncatedFrame,
+    /// A frame header had an unknown command type.
+    UnknownCommand(u8),
+    /// A protobuf message could not be decoded.
+    ProtobufDecode(prost::DecodeError),
+    /// A protobuf message was missing a field we need.
+    MissingField(&'static str),
+    /// A string table update could not be decoded.
+    StringTableCorruption(String),
+    /// Entity data did not match the send tables.
+    EntityCorruption(String),
+    /// A game event did not match its descriptor.
+    MalformedEvent(String),
+    /// Valid demo data that we are unable to decode.
+    Unsupported(String),
+    Io(io::Error),
+    /// Another error, with the location of the frame where it happened.
+    At {
+        offset: u64,
+        tick: i32,
+        error: Box<DemoError>,
+    },
+}
+
+impl DemoError {
+    /// Attach the location of the current frame, unless the error already has one.
+    pub fn at(self, offset: u64, tick: i32) -> DemoError {
+        match self {
+            DemoError::At { .. } => self,
+            error => DemoError::At {
+                offset,
+                tick,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Byte offset of the frame where the error happened, if known.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            DemoError::At { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Tick of the frame where the error happened, if known.
+    pub fn tick(&self) -> Option<i32> {
+        match self {
+            DemoError::At { tick, .. } => Some(*tick),
+            _ => None,
+        }
+    }
+
+    /// The underlying error without location information.
+    pub fn kind(&self) -> &DemoError {
+        match self {
+            DemoError::At { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for DemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemoError::BadMagic(magic) => write!(f, "bad demo magic {:?}", magic),
+            DemoError::UnsupportedProtocol(version) => {
+                write!(f, "unsupported demo protocol {}", version)
+            }
+            DemoError::TruncatedFrame => write!(f, "truncated frame"),
+            DemoError::UnknownCommand(cmd) => write!(f, "unexpected command type {}", cmd),
+            DemoError::ProtobufDecode(err) => write!(f, "protobuf decode failed: {}", err),
+            DemoError::MissingField(field) => write!(f, "missing field {}", field),
+            DemoError::StringTableCorruption(msg) => write!(f, "corrupt string table: {}", msg),
+            DemoError::EntityCorruption(msg) => write!(f, "corrupt entity data: {}", msg),
+            DemoError::MalformedEvent(msg) => write!(f, "malformed game event: {}", msg),
+            DemoError::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            DemoError::Io(err) => write!(f, "{}", err),
+            DemoError::At {
+                offset,
+                tick,
+                error,
+            } => write!(f, "{} (offset {}, tick {})", error, offset, tick),
+        }
+    }
+}
+
+impl std::error::Error for DemoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DemoError::ProtobufDecode(err) => Some(err),
+            DemoError::Io(err) => Some(err),
+            DemoError::At { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DemoError {
+    fn from(err: io::Error) -> DemoError {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            DemoError::TruncatedFrame
+        } else {
+            DemoError::Io(err)
+        }
+    }
+}
+
+impl From<prost::DecodeError> for DemoError {
+    fn from(err: prost::DecodeError) -> DemoError {
+        DemoError::ProtobufDecode(err)
+    }
+}
@@ -0,0 +1,491 @@
+use crate::datatables::*;
+use crate::error::{DemoError, Result};
+
+/// A send prop in the flattened, networked order of its class.
+#[derive(Clone, Debug)]
+pub struct FlattenedProp {
+    /// Name of the prop, prefixed with the names of the non-collapsible
+    /// tables it is in, eg. `cslocaldata.m_vecOrigin` or `m_iAmmo.001`.
+    pub name: String,
+    pub prop: SendProp,
+    /// Element type of `DPT_Array` props.
+    pub array_element: Option<SendProp>,
+}
+
+impl FlattenedProp {
+    pub fn flags(&self) -> i32 {
+        self.prop.flags.unwrap_or(0)
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.prop.priority.unwrap_or(0)
+    }
+}
+
+fn flags(prop: &SendProp) -> i32 {
+    prop.flags.unwrap_or(0)
+}
+
+fn table<'a>(data_tables: &'a DataTables, name: &str) -> Result<&'a SendTable> {
+    data_tables
+        .table(name)
+        .ok_or_else(|| DemoError::EntityCorruption(format!("unknown send table {}", name)))
+}
+
+fn gather_excludes<'a>(
+    data_tables: &'a DataTables,
+    table: &'a SendTable,
+    excludes: &mut Vec<(&'a str, &'a str)>,
+) -> Result<()> {
+    for prop in &table.props {
+        if flags(prop) & SPROP_EXCLUDE != 0 {
+            excludes.push((
+                prop.dt_name.as_deref().unwrap_or(""),
+                prop.var_name.as_deref().unwrap_or(""),
+            ));
+        }
+        if prop.r#type == Some(DPT_DATA_TABLE) {
+            let sub_table = self::table(data_tables, prop.dt_name.as_deref().unwrap_or(""))?;
+            gather_excludes(data_tables, sub_table, excludes)?;
+        }
+    }
+    Ok(())
+}
+
+fn gather_props(
+    data_tables: &DataTables,
+    table: &SendTable,
+    excludes: &[(&str, &str)],
+    prefix: &str,
+    flattened: &mut Vec<FlattenedProp>,
+) -> Result<()> {
+    let mut props = Vec::new();
+    iterate_props(data_tables, table, excludes, prefix, &mut props, flattened)?;
+    flattened.extend(props);
+    Ok(())
+}
+
+fn iterate_props(
+    data_tables: &DataTables,
+    table: &SendTable,
+    excludes: &[(&str, &str)],
+    prefix: &str,
+    props: &mut Vec<FlattenedProp>,
+    flattened: &mut Vec<FlattenedProp>,
+) -> Result<()> {
+    let table_name = table.net_table_name.as_deref().unwrap_or("");
+    for (i, prop) in table.props.iter().enumerate() {
+        let var_name = prop.var_name.as_deref().unwrap_or("");
+        if flags(prop) & (SPROP_INSIDEARRAY | SPROP_EXCLUDE) != 0
+            || excludes.contains(&(table_name, var_name))
+        {
+            continue;
+        }
+
+        if prop.r#type == Some(DPT_DATA_TABLE) {
+            let sub_table = self::table(data_tables, prop.dt_name.as_deref().unwrap_or(""))?;
+            if flags(prop) & SPROP_COLLAPSIBLE != 0 {
+                iterate_props(data_tables, sub_table, excludes, prefix, props, flattened)?;
+            } else {
+                let prefix = if var_name == "baseclass" {
+                    prefix.to_string()
+                } else {
+                    format!("{}{}.", prefix, var_name)
+                };
+                gather_props(data_tables, sub_table, excludes, &prefix, flattened)?;
+            }
+        } else {
+            let array_element = if prop.r#type == Some(DPT_ARRAY) {
+                let element = i
+                    .checked_sub(1)
+                    .and_then(|i| table.props.get(i))
+                    .ok_or_else(|| {
+                        DemoError::EntityCorruption(format!("array {} has no element", var_name))
+                    })?;
+                Some(element.clone())
+            } else {
+                None
+            };
+            props.push(FlattenedProp {
+                name: format!("{}{}", prefix, var_name),
+                prop: prop.clone(),
+                array_element,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Flatten the send table hierarchy of a class into the order the props are
+/// networked in.
+pub fn flatten(data_tables: &DataTables, table_name: &str) -> Result<Vec<FlattenedProp>> {
+    let table = self::table(data_tables, table_name)?;
+
+    let mut excludes = Vec::new();
+    gather_excludes(data_tables, table, &mut excludes)?;
+
+    let mut flattened = Vec::new();
+    gather_props(data_tables, table, &excludes, "", &mut flattened)?;
+
+    let mut priorities = flattened
+        .iter()
+        .map(|p| p.prop.priority.unwrap_or(0))
+        .chain(std::iter::once(64))
+        .collect::<Vec<_>>();
+    priorities.sort_unstable();
+    priorities.dedup();
+
+    // Not a stable sort: the engine swaps each matching prop to the front of
+    // the unsorted part, and the decoder has to match it exactly.
+    let mut start = 0;
+    for priority in priorities {
+        loop {
+            let found = (start..flattened.len()).find(|i| {
+                let prop = &flattened[*i];
+                prop.priority() == priority
+                    || (priority == 64 && prop.flags() & SPROP_CHANGES_OFTEN != 0)
+            });
+            match found {
+                Some(i) => {
+                    flattened.swap(start, i);
+                    start += 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    Ok(flattened)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::datatables::test::{encode, prop, table};
+    use crate::flatten::*;
+
+    fn sub_table(var_name: &str, dt_name: &str, flags: i32) -> SendProp {
+        SendProp {
+            dt_name: Some(dt_name.to_string()),
+            ..prop(var_name, DPT_DATA_TABLE, flags, 0)
+        }
+    }
+
+    fn exclude(dt_name: &str, var_name: &str) -> SendProp {
+        SendProp {
+            dt_name: Some(dt_name.to_string()),
+            ..prop(var_name, DPT_INT, SPROP_EXCLUDE, 0)
+        }
+    }
+
+    fn with_priority(prop: SendProp, priority: i32) -> SendProp {
+        SendProp {
+            priority: Some(priority),
+            ..prop
+        }
+    }
+
+    fn names(data_tables: &DataTables, table_name: &str) -> Vec<String> {
+        flatten(data_tables, table_name)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect()
+    }
+
+    #[test]
+    fn cs_game_rules_proxy() {
+        let data = encode(
+            &[
+                table("DT_GameRulesProxy", vec![]),
+                table(
+                    "DT_CSGameRules",
+                    vec![
+                        prop("m_bFreezePeriod", DPT_INT, SPROP_UNSIGNED, 1),
+                        prop("m_bWarmupPeriod", DPT_INT, SPROP_UNSIGNED, 1),
+                        prop("m_fRoundStartTime", DPT_FLOAT, SPROP_NOSCALE, 32),
+                        prop("m_totalRoundsPlayed", DPT_INT, 0, 16),
+                        prop("m_bBombPlanted", DPT_INT, SPROP_UNSIGNED, 1),
+                    ],
+                ),
+                table(
+                    "DT_CSGameRulesProxy",
+                    vec![
+                        sub_table("baseclass", "DT_GameRulesProxy", 0),
+                        sub_table("cs_gamerules_data", "DT_CSGameRules", 0),
+                    ],
+                ),
+            ],
+            &[(0, "CCSGameRulesProxy", "DT_CSGameRulesProxy")],
+        );
+        let data_tables = DataTables::new(&data).unwrap();
+
+        assert_eq!(
+            vec![
+                "cs_gamerules_data.m_bFreezePeriod",
+                "cs_gamerules_data.m_bWarmupPeriod",
+                "cs_gamerules_data.m_fRoundStartTime",
+                "cs_gamerules_data.m_totalRoundsPlayed",
+                "cs_gamerules_data.m_bBombPlanted",
+            ],
+            names(&data_tables, "DT_CSGameRulesProxy")
+        );
+    }
+
+    #[test]
+    fn cs_player() {
+        let data = encode(
+            &[
+                table(
+                    "DT_BaseEntity",
+                    vec![
+                        prop(
+                            "m_flSimulationTime",
+                            DPT_INT,
+                            SPROP_UNSIGNED | SPROP_CHANGES_OFTEN,
+                            8,
+                        ),
+                        prop("m_vecOrigin", DPT_VECTOR, SPROP_NOSCALE, 0),
+                        sub_table("m_Collision", "DT_CollisionProperty", SPROP_COLLAPSIBLE),
+                        prop("m_iTeamNum", DPT_INT, 0, 6),
+                    ],
+                ),
+                table(
+                    "DT_CollisionProperty",
+                    vec![
+                        prop("m_vecMins", DPT_VECTOR, SPROP_NOSCALE, 0),
+                        prop("m_vecMaxs", DPT_VECTOR, SPROP_NOSCALE, 0),
+                    ],
+                ),
+                table(
+                    "DT_LocalPlayerExclusive",
+                    vec![prop("m_nTickBase", DPT_INT, 0, 32)],
+                ),
+                table(
+                    "DT_BasePlayer",
+                    vec![
+                        sub_table("baseclass", "DT_BaseEntity", 0),
+                        sub_table("localdata", "DT_LocalPlayerExclusive", 0),
+                        prop("m_iHealth", DPT_INT, SPROP_UNSIGNED, 9),
+                    ],
+                ),
+                table(
+                    "DT_CSLocalPlayerExclusive",
+                    vec![
+                        prop("m_vecOrigin", DPT_VECTOR_XY, SPROP_NOSCALE, 0),
+                        prop("m_vecOrigin[2]", DPT_FLOAT, SPROP_NOSCALE, 0),
+                    ],
+                ),
+                table(
+                    "DT_CSPlayer",
+                    vec![
+                        sub_table("baseclass", "DT_BasePlayer", 0),
+                        exclude("DT_BaseEntity", "m_vecOrigin"),
+                        sub_table("cslocaldata", "DT_CSLocalPlayerExclusive", 0),
+                        prop("m_iAccount", DPT_INT, 0, 16),
+                        prop("m_angEyeAngles[0]", DPT_FLOAT, SPROP_CHANGES_OFTEN, 10),
+                        prop("m_iMatchStats_Kills", DPT_INT, SPROP_INSIDEARRAY, 8),
+                        SendProp {
+                            num_elements: Some(30),
+                            ..prop("m_iMatchStats_Kills", DPT_ARRAY, 0, 0)
+                        },
+                    ],
+                ),
+            ],
+            &[(0, "CCSPlayer", "DT_CSPlayer")],
+        );
+        let data_tables = DataTables::new(&data).unwrap();
+
+        // Props that change often are swapped to the front, so m_vecMins
+        // trades places with m_angEyeAngles[0].
+        assert_eq!(
+            vec![
+                "m_flSimulationTime",
+                "m_angEyeAngles[0]",
+                "m_vecMaxs",
+                "m_iTeamNum",
+                "localdata.m_nTickBase",
+                "m_iHealth",
+                "cslocaldata.m_vecOrigin",
+                "cslocaldata.m_vecOrigin[2]",
+                "m_iAccount",
+                "m_vecMins",
+                "m_iMatchStats_Kills",
+            ],
+            names(&data_tables, "DT_CSPlayer")
+        );
+
+        let flattened = flatten(&data_tables, "DT_CSPlayer").unwrap();
+        let kills = flattened.last().unwrap();
+        assert_eq!(Some(DPT_ARRAY), kills.prop.r#type);
+        let element = kills.array_element.as_ref().unwrap();
+        assert_eq!(Some(DPT_INT), element.r#type);
+        assert_eq!(Some(8), element.num_bits);
+    }
+
+    /// A subset of the real CS:GO `DT_CSPlayer` hierarchy, with the same
+    /// names, flags and table layout. Base classes are networked with
+    /// `SPROP_COLLAPSIBLE` since they sit at offset 0 of the class.
+    #[test]
+    fn cs_player_hierarchy() {
+        let changes_often =
+            |name, prop_type, flags, bits| prop(name, prop_type, flags | SPROP_CHANGES_OFTEN, bits);
+        let data = encode(
+            &[
+                table(
+                    "DT_AnimTimeMustBeFirst",
+                    vec![changes_often("m_flAnimTime", DPT_INT, SPROP_UNSIGNED, 8)],
+                ),
+                table(
+                    "DT_CollisionProperty",
+                    vec![
+                        prop("m_vecMins", DPT_VECTOR, SPROP_NOSCALE, 0),
+                        prop("m_vecMaxs", DPT_VECTOR, SPROP_NOSCALE, 0),
+                        prop("m_nSolidType", DPT_INT, SPROP_UNSIGNED, 3),
+                    ],
+                ),
+                table(
+                    "DT_BaseEntity",
+                    vec![
+                        sub_table("AnimTimeMustBeFirst", "DT_AnimTimeMustBeFirst", 0),
+                        changes_often("m_flSimulationTime", DPT_INT, SPROP_UNSIGNED, 8),
+                        prop("m_cellbits", DPT_INT, SPROP_UNSIGNED, 5),
+                        changes_often("m_vecOrigin", DPT_VECTOR, SPROP_CELL_COORD, 0),
+                        prop("m_nModelIndex", DPT_INT, 0, 13),
+                        sub_table("m_Collision", "DT_CollisionProperty", 0),
+                        prop("m_iTeamNum", DPT_INT, 0, 6),
+                        prop("m_angRotation", DPT_VECTOR, 0, 13),
+                    ],
+                ),
+                table(
+                    "DT_ServerAnimationData",
+                    vec![changes_often("m_flCycle", DPT_FLOAT, 0, 15)],
+                ),
+                table(
+                    "DT_BaseAnimating",
+                    vec![
+                        sub_table("baseclass", "DT_BaseEntity", SPROP_COLLAPSIBLE),
+                        prop("m_nSequence", DPT_INT, SPROP_UNSIGNED, 12),
+                        prop("m_nHitboxSet", DPT_INT, SPROP_UNSIGNED, 2),
+                        prop("m_flPlaybackRate", DPT_FLOAT, 0, 8),
+                        sub_table("serveranimdata", "DT_ServerAnimationData", 0),
+                    ],
+                ),
+                table(
+                    "DT_LocalPlayerExclusive",
+                    vec![
+                        prop("m_nTickBase", DPT_INT, 0, 32),
+                        changes_often("m_vecVelocity[0]", DPT_FLOAT, SPROP_NOSCALE, 32),
+                    ],
+                ),
+                table(
+                    "DT_BasePlayer",
+                    vec![
+                        sub_table("baseclass", "DT_BaseAnimating", SPROP_COLLAPSIBLE),
+                        sub_table("localdata", "DT_LocalPlayerExclusive", 0),
+                        prop("m_iHealth", DPT_INT, SPROP_UNSIGNED, 10),
+                        prop("m_lifeState", DPT_INT, SPROP_UNSIGNED, 3),
+                        prop("m_fFlags", DPT_INT, SPROP_UNSIGNED, 11),
+                    ],
+                ),
+                table(
+                    "DT_CSLocalPlayerExclusive",
+                    vec![
+                        changes_often("m_vecOrigin", DPT_VECTOR_XY, SPROP_NOSCALE, 0),
+                        changes_often("m_vecOrigin[2]", DPT_FLOAT, SPROP_NOSCALE, 0),
+                    ],
+                ),
+                table(
+                    "DT_CSNonLocalPlayerExclusive",
+                    vec![
+                        changes_often("m_vecOrigin", DPT_VECTOR_XY, SPROP_NOSCALE, 0),
+                        changes_often("m_vecOrigin[2]", DPT_FLOAT, SPROP_NOSCALE, 0),
+                    ],
+                ),
+                table(
+                    "DT_CSPlayer",
+                    vec![
+                        sub_table("baseclass", "DT_BasePlayer", SPROP_COLLAPSIBLE),
+                        exclude("DT_BaseAnimating", "m_nSequence"),
+                        exclude("DT_BaseAnimating", "m_flPlaybackRate"),
+                        exclude("DT_BaseEntity", "m_angRotation"),
+                        exclude("DT_BaseEntity", "m_vecOrigin"),
+                        exclude("DT_ServerAnimationData", "m_flCycle"),
+                        exclude("DT_AnimTimeMustBeFirst", "m_flAnimTime"),
+                        sub_table("cslocaldata", "DT_CSLocalPlayerExclusive", 0),
+                        sub_table("csnonlocaldata", "DT_CSNonLocalPlayerExclusive", 0),
+                        changes_often("m_angEyeAngles[0]", DPT_FLOAT, SPROP_NOSCALE, 32),
+                        changes_often("m_angEyeAngles[1]", DPT_FLOAT, SPROP_NOSCALE, 32),
+                        prop("m_iAccount", DPT_INT, 0, 16),
+                        prop("m_bHasHelmet", DPT_INT, SPROP_UNSIGNED, 1),
+                    ],
+                ),
+            ],
+            &[(0, "CCSPlayer", "DT_CSPlayer")],
+        );
+        let data_tables = DataTables::new(&data).unwrap();
+
+        // Non-collapsible tables come before the props of the collapsed base
+        // classes, then props that change often are swapped to the front in
+        // turn, which scatters the collision and local data props.
+        assert_eq!(
+            vec![
+                "localdata.m_vecVelocity[0]",
+                "cslocaldata.m_vecOrigin",
+                "cslocaldata.m_vecOrigin[2]",
+                "csnonlocaldata.m_vecOrigin",
+                "csnonlocaldata.m_vecOrigin[2]",
+                "m_flSimulationTime",
+                "m_angEyeAngles[0]",
+                "m_angEyeAngles[1]",
+                "m_Collision.m_vecMins",
+                "m_Collision.m_vecMaxs",
+                "m_cellbits",
+                "m_nModelIndex",
+                "m_iTeamNum",
+                "m_nHitboxSet",
+                "m_iHealth",
+                "m_lifeState",
+                "m_fFlags",
+                "m_Collision.m_nSolidType",
+                "localdata.m_nTickBase",
+                "m_iAccount",
+                "m_bHasHelmet",
+            ],
+            names(&data_tables, "DT_CSPlayer")
+        );
+    }
+
+    #[test]
+    fn priority_order() {
+        let data = encode(
+            &[table(
+                "DT_Test",
+                vec![
+                    prop("a", DPT_INT, 0, 1),
+                    with_priority(prop("b", DPT_INT, 0, 1), 0),
+                    prop("c", DPT_INT, SPROP_CHANGES_OFTEN, 1),
+                    with_priority(prop("d", DPT_INT, 0, 1), 64),
+                ],
+            )],
+            &[(0, "CTest", "DT_Test")],
+        );
+        let data_tables = DataTables::new(&data).unwrap();
+        assert_eq!(vec!["b", "c", "d", "a"], names(&data_tables, "DT_Test"));
+    }
+
+    #[test]
+    fn missing_table() {
+        let data = encode(
+            &[table(
+                "DT_Test",
+                vec![sub_table("baseclass", "DT_Missing", 0)],
+            )],
+            &[(0, "CTest", "DT_Test")],
+        );
+        let data_tables = DataTables::new(&data).unwrap();
+        assert!(matches!(
+            flatten(&data_tables, "DT_Test"),
+            Err(DemoError::EntityCorruption(_))
+        ));
+    }
+}
@@ -1,135 +1,742 @@
-use crate::csgo::netmessages_public;
-use log::{log_enabled, trace, Level};
+use crate::csgo::netmessages_public::csvc_msg_game_event::KeyT;
+use crate::csgo::netmessages_public::{CsvcMsgGameEvent, CsvcMsgGameEventList};
+use crate::error::{DemoError, Result};
+use crate::grenade::GrenadeType;
+use crate::round::{MvpReason, RoundEndReason, Side};
+use crate::weapon::Weapon;
+use log::{log_enabled, trace, warn, Level};
 use std::collections::HashMap;
 
+/// Events the built-in stats understand, converted from a [`GameEvent`].
 #[derive(Clone, Debug)]
 pub enum Event {
     Filtered,
     BeginNewMatch,
     RoundStart,
+    RoundFreezeEnd,
     RoundOfficiallyEnded,
-    RoundEnd(bool),
+    RoundEnd {
+        winner: Side,
+        reason: RoundEndReason,
+        message: String,
+    },
+    RoundMvp {
+        userid: i32,
+        reason: MvpReason,
+    },
     ItemEquip(i32, String),
+    /// User id and item of a purchase.
+    ItemPurchase(i32, Weapon),
     PlayerSpawn(i32, bool),
-    PlayerDeath {
+    PlayerConnect(i32),
+    /// User id and reason of a disconnect.
+    PlayerDisconnect(i32, String),
+    PlayerDeath(Kill),
+    PlayerHurt {
         victim: i32,
-        killer: Option<i32>,
-        assist: Option<i32>,
-        flash_assist: bool,
-        weapon: String,
+        attacker: Option<i32>,
+        /// Health left after the hit.
+        health: i32,
+        weapon: Weapon,
+        dmg_health: i32,
+        dmg_armor: i32,
+        hitgroup: HitGroup,
+    },
+    /// User id and weapon of a shot, or of a knife swing or grenade throw.
+    WeaponFire(i32, Weapon),
+    /// `site` is the entity index of the bomb target, see
+    /// [`Demo::bomb_site`](crate::Demo::bomb_site) for the site name.
+    BombBeginPlant {
+        userid: i32,
+        site: i32,
+    },
+    BombPlanted {
+        userid: i32,
+        site: i32,
+    },
+    BombBeginDefuse {
+        userid: i32,
+        has_kit: bool,
+    },
+    BombAbortDefuse(i32),
+    BombDefused {
+        userid: i32,
+        site: i32,
+    },
+    /// `userid` is the player who planted the bomb.
+    BombExploded {
+        userid: i32,
+        site: i32,
+    },
+    BombDropped(i32),
+    BombPickup(i32),
+    /// `hegrenade_detonate`, `flashbang_detonate`, `smokegrenade_detonate`,
+    /// `molotov_detonate` or `decoy_started`. Incendiaries detonate as molotovs.
+    GrenadeDetonate {
+        userid: i32,
+        kind: GrenadeType,
+        /// Entity of the grenade, not sent for molotovs.
+        entityid: Option<i32>,
+        position: [f32; 3],
+    },
+    SmokeExpired(i32),
+    /// A fire from a molotov or incendiary, which does not say who threw it.
+    InfernoStartBurn {
+        entityid: i32,
+        position: [f32; 3],
+    },
+    InfernoExpire(i32),
+    PlayerBlind {
+        userid: i32,
+        attacker: Option<i32>,
+        /// Entity of the flashbang.
+        entityid: Option<i32>,
+        /// Seconds the player is blind.
+        duration: f32,
     },
     Other(String),
 }
 
-#[derive(Clone, Debug)]
-pub struct EventContext {
-    events: HashMap<i32, (String, HashMap<usize, String>)>,
+/// Body part that was hit, from the `hitgroup` key of `player_hurt`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HitGroup {
+    /// Damage not from a bullet, like grenades, fire and falling.
+    Generic,
+    Head,
+    Chest,
+    Stomach,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+    Neck,
+    Gear,
 }
 
-impl EventContext {
-    pub fn new(events: HashMap<i32, (String, HashMap<usize, String>)>) -> EventContext {
-        EventContext { events }
+impl From<i32> for HitGroup {
+    fn from(hitgroup: i32) -> HitGroup {
+        match hitgroup {
+            1 => HitGroup::Head,
+            2 => HitGroup::Chest,
+            3 => HitGroup::Stomach,
+            4 => HitGroup::LeftArm,
+            5 => HitGroup::RightArm,
+            6 => HitGroup::LeftLeg,
+            7 => HitGroup::RightLeg,
+            8 => HitGroup::Neck,
+            10 => HitGroup::Gear,
+            _ => HitGroup::Generic,
+        }
     }
-    pub fn parse_game_event(&self, ev: netmessages_public::CsvcMsgGameEvent) -> Event {
-        let ignored = [
-            "player_footstep",
-            "weapon_fire",
-            "weapon_reload",
-            "player_hurt",
-            "item_pickup",
-        ];
-        if let Some((name, key_data)) = ev.eventid.and_then(|id| self.events.get(&id)) {
-            if ignored.contains(&name.as_str()) {
-                return Event::Filtered;
+}
+
+/// A `player_death` event. Players are identified by user id.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Kill {
+    pub victim: i32,
+    /// `None` for suicides and deaths from the world, like falling.
+    pub killer: Option<i32>,
+    pub assist: Option<i32>,
+    pub flash_assist: bool,
+    pub weapon: Weapon,
+    pub headshot: bool,
+    /// Number of objects the bullet went through.
+    pub penetrated: i32,
+    pub noscope: bool,
+    pub through_smoke: bool,
+    /// Whether the killer was blind.
+    pub attacker_blind: bool,
+    /// Whether the killer started dominating the victim.
+    pub dominated: bool,
+    /// Whether the killer ended the victim's domination over them.
+    pub revenge: bool,
+}
+
+impl Kill {
+    pub fn wallbang(&self) -> bool {
+        self.penetrated > 0
+    }
+}
+
+/// Value of a game event key, typed by the key type in the event descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventValue {
+    String(String),
+    Float(f32),
+    Long(i32),
+    Short(i16),
+    Byte(u8),
+    Bool(bool),
+    UInt64(u64),
+    WString(String),
+}
+
+impl EventValue {
+    fn new(event: &str, name: &str, key_type: i32, key: &KeyT) -> Result<EventValue> {
+        let value = match key_type {
+            1 => key.val_string.clone().map(EventValue::String),
+            2 => key.val_float.map(EventValue::Float),
+            3 => key.val_long.map(EventValue::Long),
+            4 => key.val_short.map(|n| EventValue::Short(n as i16)),
+            5 => key.val_byte.map(|n| EventValue::Byte(n as u8)),
+            6 => key.val_bool.map(EventValue::Bool),
+            7 => key.val_uint64.map(EventValue::UInt64),
+            8 => key
+                .val_wstring
+                .as_ref()
+                .map(|s| EventValue::WString(String::from_utf8_lossy(s).into_owned())),
+            _ => {
+                return Err(DemoError::MalformedEvent(format!(
+                    "{} key {} has unknown type {}",
+                    event, name, key_type
+                )))
             }
-            if log_enabled!(Level::Trace) {
-                trace!("{}", &name);
-                for (i, key) in ev.keys.iter().enumerate() {
-                    let key_name = &key_data[&i];
-                    trace!("- {} = {}", key_name, crate::parse_game::show_key(&key));
+        };
+        value.ok_or_else(|| {
+            DemoError::MalformedEvent(format!(
+                "{} key {} has no value of type {}",
+                event, name, key_type
+            ))
+        })
+    }
+
+    /// Integer value of a long, short or byte key.
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            EventValue::Long(n) => Some(*n),
+            EventValue::Short(n) => Some(*n as i32),
+            EventValue::Byte(n) => Some(*n as i32),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            EventValue::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            EventValue::UInt64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Value of a bool key, or of an integer key used as a flag.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            EventValue::Bool(b) => Some(*b),
+            other => other.as_i32().map(|n| n != 0),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            EventValue::String(s) | EventValue::WString(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A game event with its keys named and typed from the `svc_GameEventList`
+/// descriptors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameEvent {
+    pub name: String,
+    pub tick: i32,
+    pub fields: HashMap<String, EventValue>,
+}
+
+impl GameEvent {
+    pub fn get(&self, key: &str) -> Option<&EventValue> {
+        self.fields.get(key)
+    }
+
+    pub fn get_i32(&self, key: &str) -> Option<i32> {
+        self.get(key).and_then(EventValue::as_i32)
+    }
+
+    pub fn get_f32(&self, key: &str) -> Option<f32> {
+        self.get(key).and_then(EventValue::as_f32)
+    }
+
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(EventValue::as_u64)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(EventValue::as_bool)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(EventValue::as_str)
+    }
+
+    fn require_i32(&self, key: &str) -> Result<i32> {
+        self.get_i32(key)
+            .ok_or_else(|| missing_key(&self.name, key))
+    }
+
+    fn require_position(&self) -> Result<[f32; 3]> {
+        let coord = |key| {
+            self.get_f32(key)
+                .ok_or_else(|| missing_key(&self.name, key))
+        };
+        Ok([coord("x")?, coord("y")?, coord("z")?])
+    }
+
+    fn require_str(&self, key: &str) -> Result<&str> {
+        self.get_str(key)
+            .ok_or_else(|| missing_key(&self.name, key))
+    }
+}
+
+fn missing_key(event: &str, key: &str) -> DemoError {
+    DemoError::MalformedEvent(format!("{} is missing key {}", event, key))
+}
+
+impl Event {
+    /// Typed form of a game event. Events with a key missing or of another
+    /// type than expected are logged and become [`Event::Other`].
+    pub fn from_game_event(event: &GameEvent) -> Event {
+        Event::typed(event).unwrap_or_else(|err| {
+            warn!("{}", err);
+            Event::Other(event.name.clone())
+        })
+    }
+
+    fn typed(event: &GameEvent) -> Result<Event> {
+        let name = event.name.as_str();
+        Ok(match name {
+            "begin_new_match" => Event::BeginNewMatch,
+            "round_announce_match_start" | "round_start" => Event::RoundStart,
+            "round_freeze_end" => Event::RoundFreezeEnd,
+            "round_officially_ended" => Event::RoundOfficiallyEnded,
+            "round_end" => match Side::from_team(event.require_i32("winner")?) {
+                Some(winner) => Event::RoundEnd {
+                    winner,
+                    reason: RoundEndReason::from(event.get_i32("reason").unwrap_or(0)),
+                    message: event.get_str("message").unwrap_or("").to_string(),
+                },
+                None => Event::Filtered,
+            },
+            "round_mvp" => Event::RoundMvp {
+                userid: event.require_i32("userid")?,
+                reason: MvpReason::from(event.get_i32("reason").unwrap_or(0)),
+            },
+            "item_equip" => Event::ItemEquip(
+                event.require_i32("userid")?,
+                event.require_str("item")?.to_string(),
+            ),
+            "item_purchase" => Event::ItemPurchase(
+                event.require_i32("userid")?,
+                Weapon::from_name(event.require_str("weapon")?),
+            ),
+            "player_spawn" => {
+                let userid = event.require_i32("userid")?;
+                match event.require_i32("teamnum")? {
+                    2 => Event::PlayerSpawn(userid, true),
+                    3 => Event::PlayerSpawn(userid, false),
+                    _ => Event::Filtered,
                 }
             }
-            match name.as_str() {
-                "begin_new_match" => Event::BeginNewMatch,
-                "round_announce_match_start" | "round_start" => Event::RoundStart,
-                "round_officially_ended" => Event::RoundOfficiallyEnded,
-                "round_end" => {
-                    let mut winner_team = None;
-                    for (i, key) in ev.keys.iter().enumerate() {
-                        let key_name = &key_data[&i];
-                        if key_name == "winner" {
-                            winner_team = key.val_byte;
-                        }
-                    }
-                    let team = winner_team.filter(|t| *t == 2 || *t == 3).unwrap();
-                    Event::RoundEnd(team == 2)
-                }
-                "item_equip" => {
-                    let mut item = None;
-                    let mut userid = None;
-                    for (i, key) in ev.keys.iter().enumerate() {
-                        let key_name = &key_data[&i];
-                        if key_name == "item" {
-                            item = Some(key.val_string.clone().unwrap());
-                        } else if key_name == "userid" {
-                            userid = key.val_short;
-                        }
-                    }
-                    Event::ItemEquip(userid.unwrap(), item.unwrap())
+            "player_connect" => Event::PlayerConnect(event.require_i32("userid")?),
+            "player_disconnect" => Event::PlayerDisconnect(
+                event.require_i32("userid")?,
+                event.get_str("reason").unwrap_or_default().to_string(),
+            ),
+            "player_death" => {
+                let flag = |key| event.get_bool(key).unwrap_or(false);
+                Event::PlayerDeath(Kill {
+                    victim: event.require_i32("userid")?,
+                    killer: event.get_i32("attacker").filter(|id| *id > 0),
+                    assist: event.get_i32("assister").filter(|id| *id > 0),
+                    flash_assist: flag("assistedflash"),
+                    weapon: Weapon::from_name(event.get_str("weapon").unwrap_or("")),
+                    headshot: flag("headshot"),
+                    penetrated: event.get_i32("penetrated").unwrap_or(0),
+                    noscope: flag("noscope"),
+                    through_smoke: flag("thrusmoke"),
+                    attacker_blind: flag("attackerblind"),
+                    dominated: flag("dominated"),
+                    revenge: flag("revenge"),
+                })
+            }
+            "player_hurt" => Event::PlayerHurt {
+                victim: event.require_i32("userid")?,
+                attacker: event.get_i32("attacker").filter(|id| *id > 0),
+                health: event.require_i32("health")?,
+                weapon: Weapon::from_name(event.get_str("weapon").unwrap_or("")),
+                dmg_health: event.require_i32("dmg_health")?,
+                dmg_armor: event.get_i32("dmg_armor").unwrap_or(0),
+                hitgroup: HitGroup::from(event.get_i32("hitgroup").unwrap_or(0)),
+            },
+            "weapon_fire" => Event::WeaponFire(
+                event.require_i32("userid")?,
+                Weapon::from_name(event.require_str("weapon")?),
+            ),
+            "bomb_beginplant" => Event::BombBeginPlant {
+                userid: event.require_i32("userid")?,
+                site: event.require_i32("site")?,
+            },
+            "bomb_planted" => Event::BombPlanted {
+                userid: event.require_i32("userid")?,
+                site: event.require_i32("site")?,
+            },
+            "bomb_begindefuse" => Event::BombBeginDefuse {
+                userid: event.require_i32("userid")?,
+                has_kit: event.get_bool("haskit").unwrap_or(false),
+            },
+            "bomb_abortdefuse" => Event::BombAbortDefuse(event.require_i32("userid")?),
+            "bomb_defused" => Event::BombDefused {
+                userid: event.require_i32("userid")?,
+                site: event.require_i32("site")?,
+            },
+            "bomb_exploded" => Event::BombExploded {
+                userid: event.require_i32("userid")?,
+                site: event.require_i32("site")?,
+            },
+            "bomb_dropped" => Event::BombDropped(event.require_i32("userid")?),
+            "bomb_pickup" => Event::BombPickup(event.require_i32("userid")?),
+            "hegrenade_detonate"
+            | "flashbang_detonate"
+            | "smokegrenade_detonate"
+            | "molotov_detonate"
+            | "decoy_started" => Event::GrenadeDetonate {
+                userid: event.require_i32("userid")?,
+                kind: match name {
+                    "hegrenade_detonate" => GrenadeType::HighExplosive,
+                    "flashbang_detonate" => GrenadeType::Flashbang,
+                    "smokegrenade_detonate" => GrenadeType::Smoke,
+                    "molotov_detonate" => GrenadeType::Molotov,
+                    _ => GrenadeType::Decoy,
+                },
+                entityid: event.get_i32("entityid"),
+                position: event.require_position()?,
+            },
+            "smokegrenade_expired" => Event::SmokeExpired(event.require_i32("entityid")?),
+            "inferno_startburn" => Event::InfernoStartBurn {
+                entityid: event.require_i32("entityid")?,
+                position: event.require_position()?,
+            },
+            "player_blind" => Event::PlayerBlind {
+                userid: event.require_i32("userid")?,
+                attacker: event.get_i32("attacker").filter(|id| *id > 0),
+                entityid: event.get_i32("entityid"),
+                duration: event.get_f32("blind_duration").unwrap_or(0.0),
+            },
+            "inferno_expire" => Event::InfernoExpire(event.require_i32("entityid")?),
+            name => Event::Other(name.to_string()),
+        })
+    }
+}
+
+/// Key types that [`EventValue`] can hold.
+const KNOWN_KEY_TYPES: std::ops::RangeInclusive<i32> = 1..=8;
+
+/// Name and key names and types of one event, from `svc_GameEventList`.
+#[derive(Clone, Debug)]
+struct EventDescriptor {
+    name: String,
+    keys: Vec<(String, i32)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EventContext {
+    events: HashMap<i32, EventDescriptor>,
+}
+
+impl EventContext {
+    /// Read the event descriptors. Descriptors without an id or name are
+    /// logged and skipped, as are the values of keys with an unknown type.
+    pub fn new(list: CsvcMsgGameEventList) -> EventContext {
+        let mut events = HashMap::new();
+        for event in list.descriptors {
+            let (id, name) = match (event.eventid, event.name) {
+                (Some(id), Some(name)) => (id, name),
+                (id, name) => {
+                    warn!(
+                        "Skipping event descriptor {:?} {:?} without id or name",
+                        id, name
+                    );
+                    continue;
                 }
-                "player_spawn" => {
-                    let mut userid = None;
-                    let mut teamnum = None;
-                    for (i, key) in ev.keys.iter().enumerate() {
-                        let key_name = &key_data[&i];
-                        if key_name == "userid" {
-                            userid = key.val_short;
-                        } else if key_name == "teamnum" {
-                            teamnum = key.val_short;
-                        }
-                    }
-                    if teamnum.unwrap() == 2 || teamnum.unwrap() == 3 {
-                        Event::PlayerSpawn(userid.unwrap(), teamnum.unwrap() == 2)
-                    } else {
-                        Event::Filtered
+            };
+            let keys = event
+                .keys
+                .into_iter()
+                .map(|key| {
+                    let key_name = key.name.unwrap_or_default();
+                    let key_type = key.r#type.unwrap_or(0);
+                    if !KNOWN_KEY_TYPES.contains(&key_type) {
+                        // Keep the key so the ones after it still line up
+                        warn!("{} key {} has unknown type {}", name, key_name, key_type);
                     }
+                    (key_name, key_type)
+                })
+                .collect();
+            events.insert(id, EventDescriptor { name, keys });
+        }
+        EventContext { events }
+    }
+
+    /// Name the keys of an event and type their values. Keys that do not
+    /// match the descriptor are logged and left out, and events with an
+    /// unknown id have no keys.
+    pub fn parse_game_event(&self, ev: CsvcMsgGameEvent, tick: i32) -> GameEvent {
+        let descriptor = match ev.eventid.and_then(|id| self.events.get(&id)) {
+            Some(descriptor) => descriptor,
+            None => {
+                match &ev.event_name {
+                    Some(name) => trace!("{}", name),
+                    None => warn!("Unknown event id {:?}", ev.eventid),
                 }
-                "player_death" => {
-                    let mut userid = None;
-                    let mut attackerid = None;
-                    let mut assisterid = None;
-                    let mut assisterflash = None;
-                    let mut weapon: Option<&str> = None;
-                    for (i, key) in ev.keys.iter().enumerate() {
-                        let key_name = &key_data[&i];
-                        if key_name == "userid" {
-                            userid = key.val_short;
-                        } else if key_name == "attacker" {
-                            attackerid = key.val_short;
-                        } else if key_name == "assister" {
-                            assisterid = key.val_short;
-                        } else if key_name == "assistedflash" {
-                            assisterflash = key.val_bool;
-                        } else if key_name == "weapon" {
-                            weapon = key.val_string.as_ref().map(|s| s.as_str());
-                        }
-                    }
-                    let id = userid.unwrap();
-                    Event::PlayerDeath {
-                        victim: id,
-                        killer: attackerid.filter(|id| *id > 0),
-                        assist: assisterid.filter(|id| *id > 0),
-                        flash_assist: assisterflash.unwrap_or(false),
-                        weapon: weapon.unwrap_or("").to_string(),
-                    }
+                return GameEvent {
+                    name: ev.event_name.unwrap_or_default(),
+                    tick,
+                    fields: HashMap::new(),
+                };
+            }
+        };
+        let name = &descriptor.name;
+        if ev.keys.len() > descriptor.keys.len() {
+            warn!(
+                "{} has {} keys, expected {}",
+                name,
+                ev.keys.len(),
+                descriptor.keys.len()
+            );
+        }
+        let mut fields = HashMap::new();
+        for ((key_name, key_type), key) in descriptor.keys.iter().zip(&ev.keys) {
+            if !KNOWN_KEY_TYPES.contains(key_type) {
+                continue;
+            }
+            match EventValue::new(name, key_name, *key_type, key) {
+                Ok(value) => {
+                    fields.insert(key_name.clone(), value);
                 }
-                name => Event::Other(name.to_string()),
+                Err(err) => warn!("{}", err),
+            }
+        }
+        if log_enabled!(Level::Trace) {
+            trace!("{}", name);
+            for (key_name, value) in &fields {
+                trace!("- {} = {:?}", key_name, value);
             }
-        } else if let Some(name) = ev.event_name {
-            trace!("{}", &name);
-            Event::Other(name)
-        } else {
-            panic!("{:?}", ev);
         }
+        GameEvent {
+            name: name.clone(),
+            tick,
+            fields,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::csgo::netmessages_public::csvc_msg_game_event_list::{DescriptorT, KeyT as KeyDesc};
+    use crate::game_event::*;
+
+    /// Descriptor list with one event per entry, numbered from 1.
+    pub(crate) fn event_list(events: &[(&str, &[(&str, i32)])]) -> CsvcMsgGameEventList {
+        CsvcMsgGameEventList {
+            descriptors: events
+                .iter()
+                .enumerate()
+                .map(|(i, (name, keys))| DescriptorT {
+                    eventid: Some(i as i32 + 1),
+                    name: Some(name.to_string()),
+                    keys: keys
+                        .iter()
+                        .map(|(name, key_type)| KeyDesc {
+                            r#type: Some(*key_type),
+                            name: Some(name.to_string()),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn game_event(id: i32, keys: Vec<KeyT>) -> CsvcMsgGameEvent {
+        CsvcMsgGameEvent {
+            eventid: Some(id),
+            keys,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn typed_values() {
+        let context = EventContext::new(event_list(&[
+            (
+                "player_death",
+                &[
+                    ("userid", 4),
+                    ("attacker", 4),
+                    ("assister", 4),
+                    ("assistedflash", 6),
+                    ("weapon", 1),
+                    ("penetrated", 5),
+                ],
+            ),
+            (
+                "other_death",
+                &[("entindex", 3), ("x", 2), ("xuid", 7), ("msg", 8)],
+            ),
+        ]));
+
+        let death = context.parse_game_event(
+            game_event(
+                1,
+                vec![
+                    KeyT {
+                        val_short: Some(3),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_short: Some(7),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_short: Some(0),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_bool: Some(true),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_string: Some("ak47".to_string()),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_byte: Some(1),
+                        ..Default::default()
+                    },
+                ],
+            ),
+            1234,
+        );
+        assert_eq!("player_death", death.name);
+        assert_eq!(1234, death.tick);
+        assert_eq!(Some(&EventValue::Short(3)), death.get("userid"));
+        assert_eq!(Some(7), death.get_i32("attacker"));
+        assert_eq!(Some("ak47"), death.get_str("weapon"));
+        assert_eq!(Some(true), death.get_bool("assistedflash"));
+        assert_eq!(Some(true), death.get_bool("penetrated"));
+        assert_eq!(None, death.get_i32("weapon"));
+        assert_eq!(None, death.get("headshot"));
+
+        match Event::from_game_event(&death) {
+            Event::PlayerDeath(kill) => {
+                assert_eq!(
+                    Kill {
+                        victim: 3,
+                        killer: Some(7),
+                        assist: None,
+                        flash_assist: true,
+                        weapon: Weapon::Ak47,
+                        penetrated: 1,
+                        ..Kill::default()
+                    },
+                    kill
+                );
+                assert!(kill.wallbang());
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        let other = context.parse_game_event(
+            game_event(
+                2,
+                vec![
+                    KeyT {
+                        val_long: Some(-2),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_float: Some(1.5),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_uint64: Some(76561197960265728),
+                        ..Default::default()
+                    },
+                    KeyT {
+                        val_wstring: Some(b"hi".to_vec()),
+                        ..Default::default()
+                    },
+                ],
+            ),
+            0,
+        );
+        assert_eq!(Some(-2), other.get_i32("entindex"));
+        assert_eq!(Some(1.5), other.get_f32("x"));
+        assert_eq!(Some(76561197960265728), other.get_u64("xuid"));
+        assert_eq!(
+            Some(&EventValue::WString("hi".to_string())),
+            other.get("msg")
+        );
+        assert!(matches!(
+            Event::from_game_event(&other),
+            Event::Other(name) if name == "other_death"
+        ));
+    }
+
+    #[test]
+    fn malformed_events() {
+        let context = EventContext::new(event_list(&[(
+            "player_spawn",
+            &[("userid", 4), ("teamnum", 4)],
+        )]));
+        let short = |n| KeyT {
+            val_short: Some(n),
+            ..Default::default()
+        };
+
+        // A value of another type than the descriptor says is left out
+        let mistyped = KeyT {
+            val_string: Some("3".to_string()),
+            ..Default::default()
+        };
+        let spawn = context.parse_game_event(game_event(1, vec![mistyped, short(2)]), 0);
+        assert_eq!("player_spawn", spawn.name);
+        assert_eq!(None, spawn.get("userid"));
+        assert_eq!(Some(2), spawn.get_i32("teamnum"));
+        assert!(matches!(
+            Event::from_game_event(&spawn),
+            Event::Other(name) if name == "player_spawn"
+        ));
+
+        // Extra keys are ignored
+        let spawn = context.parse_game_event(game_event(1, vec![short(3), short(2), short(1)]), 0);
+        assert!(matches!(
+            Event::from_game_event(&spawn),
+            Event::PlayerSpawn(3, true)
+        ));
+
+        let unknown = context.parse_game_event(game_event(9, vec![short(1)]), 0);
+        assert!(unknown.fields.is_empty());
+
+        // Broken descriptors are skipped, the others still work
+        let mut list = event_list(&[
+            ("player_spawn", &[("userid", 9), ("teamnum", 4)]),
+            ("round_start", &[]),
+            ("player_jump", &[("userid", 4)]),
+        ]);
+        list.descriptors[1].name = None;
+        let context = EventContext::new(list);
+        let spawn = context.parse_game_event(game_event(1, vec![short(3), short(2)]), 0);
+        assert_eq!(None, spawn.get("userid"));
+        assert_eq!(Some(2), spawn.get_i32("teamnum"));
+        let nameless = context.parse_game_event(game_event(2, vec![]), 0);
+        assert_eq!("", nameless.name);
+        let jump = context.parse_game_event(game_event(3, vec![short(5)]), 0);
+        assert_eq!("player_jump", jump.name);
+        assert_eq!(Some(5), jump.get_i32("userid"));
     }
 }
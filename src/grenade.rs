@@ -0,0 +1,282 @@
+/// Kind of a thrown grenade.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GrenadeType {
+    HighExplosive,
+    Flashbang,
+    Smoke,
+    Molotov,
+    Incendiary,
+    Decoy,
+}
+
+impl GrenadeType {
+    /// Whether a detonation event of this type can be from a grenade of the
+    /// other type. Incendiaries detonate with `molotov_detonate`.
+    fn matches(self, other: GrenadeType) -> bool {
+        self == other
+            || matches!(
+                (self, other),
+                (GrenadeType::Molotov, GrenadeType::Incendiary)
+            )
+    }
+
+    fn is_fire(self) -> bool {
+        matches!(self, GrenadeType::Molotov | GrenadeType::Incendiary)
+    }
+}
+
+/// A player blinded by a flashbang.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blind {
+    /// First user id of the player.
+    pub victim: i32,
+    pub tick: i32,
+    /// Seconds the player was blind.
+    pub duration: f32,
+}
+
+/// One grenade, from the throw to the end of its effect.
+///
+/// The thrower is the first user id of the player, see
+/// [`Player::user_ids`](crate::Player::user_ids).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grenade {
+    pub kind: GrenadeType,
+    pub thrower: i32,
+    /// Round the grenade was thrown in, counting from 1.
+    pub round: i32,
+    /// `None` if the throw was not seen.
+    pub throw_tick: Option<i32>,
+    /// `None` if the grenade never went off, eg. because the round ended.
+    pub detonate_tick: Option<i32>,
+    pub position: Option<[f32; 3]>,
+    /// Tick the smoke faded or the fire burnt out.
+    pub expire_tick: Option<i32>,
+    /// Entity of the grenade, or of the fire for molotovs and incendiaries.
+    pub entity_id: Option<i32>,
+    /// Players blinded by a flashbang.
+    pub blinded: Vec<Blind>,
+}
+
+/// Grenades of a match, matching detonations to the throws before them.
+#[derive(Clone, Debug, Default)]
+pub struct Grenades {
+    grenades: Vec<Grenade>,
+    /// Blinds by flashbang entity, sent before the flashbang detonated.
+    pending_blinds: Vec<(i32, Blind)>,
+}
+
+impl Grenades {
+    pub fn throw(&mut self, thrower: i32, kind: GrenadeType, round: i32, tick: i32) {
+        self.grenades.push(Grenade {
+            kind,
+            thrower,
+            round,
+            throw_tick: Some(tick),
+            detonate_tick: None,
+            position: None,
+            expire_tick: None,
+            entity_id: None,
+            blinded: Vec::new(),
+        });
+    }
+
+    /// Record a detonation, for the oldest throw of the round that has not
+    /// gone off yet.
+    pub fn detonate(
+        &mut self,
+        thrower: i32,
+        kind: GrenadeType,
+        round: i32,
+        tick: i32,
+        entity_id: Option<i32>,
+        position: [f32; 3],
+    ) -> &Grenade {
+        let thrown = self.grenades.iter().position(|g| {
+            g.thrower == thrower
+                && g.round == round
+                && g.detonate_tick.is_none()
+                && kind.matches(g.kind)
+        });
+        let i = match thrown {
+            Some(i) => i,
+            None => {
+                self.grenades.push(Grenade {
+                    kind,
+                    thrower,
+                    round,
+                    throw_tick: None,
+                    detonate_tick: None,
+                    position: None,
+                    expire_tick: None,
+                    entity_id: None,
+                    blinded: Vec::new(),
+                });
+                self.grenades.len() - 1
+            }
+        };
+        let grenade = &mut self.grenades[i];
+        grenade.detonate_tick = Some(tick);
+        grenade.position = Some(position);
+        grenade.entity_id = entity_id;
+        if kind == GrenadeType::Flashbang {
+            let pending = std::mem::take(&mut self.pending_blinds);
+            let (blinded, pending) = pending
+                .into_iter()
+                .partition(|(id, _)| Some(*id) == entity_id);
+            self.pending_blinds = pending;
+            grenade
+                .blinded
+                .extend(blinded.into_iter().map(|(_, blind)| blind));
+        }
+        grenade
+    }
+
+    /// Record a player blinded by the flashbang with the given entity, which
+    /// may not have detonated yet.
+    pub fn blind(&mut self, entity_id: i32, blind: Blind) {
+        let flashbang = self.grenades.iter_mut().rev().find(|g| {
+            g.kind == GrenadeType::Flashbang
+                && g.entity_id == Some(entity_id)
+                && g.detonate_tick.is_some()
+        });
+        match flashbang {
+            Some(flashbang) => flashbang.blinded.push(blind),
+            None => self.pending_blinds.push((entity_id, blind)),
+        }
+    }
+
+    /// Link a fire to the molotov or incendiary of the round that detonated
+    /// closest to it.
+    pub fn start_fire(&mut self, entity_id: i32, round: i32, position: [f32; 3]) {
+        let distance = |g: &Grenade| {
+            g.position
+                .map(|p| (0..3).map(|i| (p[i] - position[i]).powi(2)).sum::<f32>())
+                .unwrap_or(f32::INFINITY)
+        };
+        let fire = self
+            .grenades
+            .iter_mut()
+            .filter(|g| {
+                g.kind.is_fire()
+                    && g.round == round
+                    && g.position.is_some()
+                    && g.entity_id.is_none()
+            })
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+        if let Some(fire) = fire {
+            fire.entity_id = Some(entity_id);
+        }
+    }
+
+    /// Record the end of the smoke or fire with the given entity.
+    pub fn expire(&mut self, entity_id: i32, tick: i32) {
+        let grenade = self
+            .grenades
+            .iter_mut()
+            .rev()
+            .find(|g| g.entity_id == Some(entity_id) && g.expire_tick.is_none());
+        if let Some(grenade) = grenade {
+            grenade.expire_tick = Some(tick);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.grenades.clear();
+        self.pending_blinds.clear();
+    }
+
+    pub fn into_vec(self) -> Vec<Grenade> {
+        self.grenades
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grenade::*;
+
+    #[test]
+    fn lifecycle() {
+        let mut grenades = Grenades::default();
+        grenades.throw(1, GrenadeType::Smoke, 3, 100);
+        grenades.throw(1, GrenadeType::Smoke, 3, 150);
+        grenades.throw(2, GrenadeType::Incendiary, 3, 160);
+        grenades.throw(2, GrenadeType::Molotov, 3, 170);
+
+        let smoke = grenades.detonate(1, GrenadeType::Smoke, 3, 300, Some(80), [1.0, 2.0, 3.0]);
+        assert_eq!(Some(100), smoke.throw_tick);
+        grenades.detonate(1, GrenadeType::Smoke, 3, 350, Some(81), [4.0, 5.0, 6.0]);
+        grenades.expire(80, 1500);
+
+        // Incendiaries detonate as molotovs, and fires do not say who threw them
+        grenades.detonate(2, GrenadeType::Molotov, 3, 200, None, [100.0, 0.0, 0.0]);
+        grenades.detonate(2, GrenadeType::Molotov, 3, 210, None, [-100.0, 0.0, 0.0]);
+        grenades.start_fire(90, 3, [-90.0, 0.0, 0.0]);
+        grenades.start_fire(91, 3, [110.0, 0.0, 0.0]);
+        grenades.expire(91, 700);
+
+        // A detonation without a throw still gets a record
+        grenades.detonate(3, GrenadeType::HighExplosive, 3, 400, Some(82), [0.0; 3]);
+
+        let summary = grenades
+            .grenades
+            .iter()
+            .map(|g| (g.kind, g.thrower, g.throw_tick, g.entity_id, g.expire_tick))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (GrenadeType::Smoke, 1, Some(100), Some(80), Some(1500)),
+                (GrenadeType::Smoke, 1, Some(150), Some(81), None),
+                (GrenadeType::Incendiary, 2, Some(160), Some(91), Some(700)),
+                (GrenadeType::Molotov, 2, Some(170), Some(90), None),
+                (GrenadeType::HighExplosive, 3, None, Some(82), None),
+            ],
+            summary
+        );
+    }
+
+    #[test]
+    fn fire_from_the_current_round() {
+        let mut grenades = Grenades::default();
+        // This molotov's fire was never seen, eg. because it landed in water
+        grenades.throw(1, GrenadeType::Molotov, 2, 100);
+        grenades.detonate(1, GrenadeType::Molotov, 2, 200, None, [0.0; 3]);
+        grenades.throw(2, GrenadeType::Molotov, 3, 1100);
+        grenades.detonate(2, GrenadeType::Molotov, 3, 1200, None, [500.0, 0.0, 0.0]);
+
+        grenades.start_fire(90, 3, [10.0, 0.0, 0.0]);
+
+        let linked = grenades
+            .grenades
+            .iter()
+            .map(|g| (g.thrower, g.entity_id))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(1, None), (2, Some(90))], linked);
+    }
+
+    #[test]
+    fn blinds() {
+        let blind = |victim| Blind {
+            victim,
+            tick: 100,
+            duration: 2.5,
+        };
+        let mut grenades = Grenades::default();
+        grenades.throw(1, GrenadeType::Flashbang, 1, 50);
+        grenades.throw(1, GrenadeType::Flashbang, 1, 60);
+        // Blinds are sent just before the detonation
+        grenades.blind(70, blind(2));
+        grenades.blind(71, blind(3));
+        grenades.detonate(1, GrenadeType::Flashbang, 1, 100, Some(70), [0.0; 3]);
+        grenades.detonate(1, GrenadeType::Flashbang, 1, 110, Some(71), [0.0; 3]);
+        grenades.blind(71, blind(4));
+
+        let blinded = grenades
+            .grenades
+            .iter()
+            .map(|g| g.blinded.iter().map(|b| b.victim).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec![2], vec![3, 4]], blinded);
+        assert!(grenades.pending_blinds.is_empty());
+    }
+}
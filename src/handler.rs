@@ -0,0 +1,49 @@
+use crate::csgo::netmessages_public::CsvcMsgUserMessage;
+use crate::demo::Demo;
+use crate::game_event::{Event, GameEvent};
+use crate::header::Header;
+use crate::playerinfo::PlayerInfo;
+use crate::serverinfo::ServerInfo;
+use crate::stringtables::StringTableEntry;
+
+/// Callbacks invoked by [`parse_demo`](crate::parse_demo) as a demo is read.
+///
+/// Every method has an empty default, so a handler only implements the ones
+/// it cares about. `demo` is the parser state after the message was applied.
+#[allow(unused_variables)]
+pub trait DemoHandler {
+    /// Called once, before any frame is read.
+    fn on_header(&mut self, header: &Header) {}
+
+    /// Called at the start of every frame whose tick differs from the last one.
+    fn on_tick(&mut self, demo: &Demo, tick: i32) {}
+
+    /// Called when `svc_ServerInfo` is received, after it is stored in `demo`.
+    fn on_server_info(&mut self, demo: &Demo, info: &ServerInfo) {}
+
+    /// Called for every convar set with `net_SetConVar`.
+    fn on_convar(&mut self, demo: &Demo, name: &str, value: &str) {}
+
+    /// Called for every game event, with its keys named and typed.
+    fn on_game_event(&mut self, demo: &Demo, event: &GameEvent) {}
+
+    /// Called after `on_game_event` with the event converted to an [`Event`].
+    fn on_event(&mut self, demo: &Demo, event: &Event) {}
+
+    fn on_user_message(&mut self, demo: &Demo, msg: &CsvcMsgUserMessage) {}
+
+    /// Called with the entries of a string table that were created or changed.
+    fn on_string_table_update(
+        &mut self,
+        demo: &Demo,
+        table: &str,
+        changes: &[(i64, StringTableEntry)],
+    ) {
+    }
+
+    /// Called whenever a player's `userinfo` entry is created or updated.
+    fn on_player_info(&mut self, demo: &Demo, info: &PlayerInfo) {}
+
+    /// Called after every frame.
+    fn on_frame_end(&mut self, demo: &Demo) {}
+}
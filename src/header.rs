@@ -1,6 +1,7 @@
 use std::io::Read;
 
 use crate::bitreader::{string_from_nilslice, ReadExtras};
+use crate::error::{DemoError, Result};
 
 #[derive(Clone, Debug, Default)]
 pub struct Header {
@@ -18,19 +19,25 @@ pub struct Header {
 }
 
 impl Header {
-    pub fn new<R: Read>(reader: &mut R) -> Header {
-        let magic = string_from_nilslice(&reader.read_u8_vec(8).unwrap());
-        let demo_protocol = reader.read_i32().unwrap();
-        let network_protocol = reader.read_i32().unwrap();
-        let server_name = string_from_nilslice(&reader.read_u8_vec(260).unwrap());
-        let client_name = string_from_nilslice(&reader.read_u8_vec(260).unwrap());
-        let map = string_from_nilslice(&reader.read_u8_vec(260).unwrap());
-        let directory = string_from_nilslice(&reader.read_u8_vec(260).unwrap());
-        let playback_time = reader.read_f32().unwrap();
-        let playback_ticks = reader.read_i32().unwrap();
-        let playback_frames = reader.read_i32().unwrap();
-        let signon_length = reader.read_i32().unwrap();
-        let header = Header {
+    pub fn new<R: Read>(reader: &mut R) -> Result<Header> {
+        let magic = string_from_nilslice(&reader.read_u8_vec(8)?);
+        if magic != "HL2DEMO" {
+            return Err(DemoError::BadMagic(magic));
+        }
+        let demo_protocol = reader.read_i32()?;
+        if demo_protocol != 4 {
+            return Err(DemoError::UnsupportedProtocol(demo_protocol));
+        }
+        let network_protocol = reader.read_i32()?;
+        let server_name = string_from_nilslice(&reader.read_u8_vec(260)?);
+        let client_name = string_from_nilslice(&reader.read_u8_vec(260)?);
+        let map = string_from_nilslice(&reader.read_u8_vec(260)?);
+        let directory = string_from_nilslice(&reader.read_u8_vec(260)?);
+        let playback_time = reader.read_f32()?;
+        let playback_ticks = reader.read_i32()?;
+        let playback_frames = reader.read_i32()?;
+        let signon_length = reader.read_i32()?;
+        Ok(Header {
             magic,
             demo_protocol,
             network_protocol,
@@ -42,14 +49,11 @@ impl Header {
             playback_ticks,
             playback_frames,
             signon_length,
-        };
-
-        assert_eq!(header.magic, "HL2DEMO");
-        assert_eq!(header.demo_protocol, 4);
-
-        header
+        })
     }
 
+    /// Tickrate estimated from the playback length. Prefer the tickrate from
+    /// [`ServerInfo`](crate::ServerInfo), this is often off by one.
     pub fn tickrate(&self) -> i32 {
         ((self.playback_ticks as f32) / self.playback_time) as i32
     }
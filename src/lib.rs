@@ -0,0 +1,53 @@
+//! CS:GO demo file parser.
+//!
+//! The main entry point is [`parse_game`], which reads a whole demo and
+//! returns a [`MatchSummary`] with the final score and per-player stats.
+
+mod bitreader;
+mod bomb;
+mod cmd;
+mod convars;
+pub mod csgo;
+mod datatables;
+mod demo;
+mod economy;
+mod entities;
+mod error;
+mod flatten;
+mod game_event;
+mod grenade;
+mod handler;
+mod header;
+mod packet;
+mod parse_game;
+mod player;
+mod playerinfo;
+mod propdecode;
+mod reader;
+mod round;
+mod serverinfo;
+mod stringtables;
+mod weapon;
+
+pub use bomb::{BombRound, BombSite};
+pub use convars::{ConVars, MatchRules, Period};
+pub use datatables::{DataTables, SendProp, SendTable, ServerClass};
+pub use demo::{parse_demo, Demo};
+pub use economy::{EconomyRound, PlayerEconomy, TeamEconomy};
+pub use entities::{Entities, Entity, EntityClass};
+pub use error::{DemoError, Result};
+pub use flatten::{flatten, FlattenedProp};
+pub use game_event::{Event, EventValue, GameEvent, HitGroup, Kill};
+pub use grenade::{Blind, Grenade, GrenadeType};
+pub use handler::DemoHandler;
+pub use header::Header;
+pub use packet::{CmdType, ConsoleCmd, CustomData, DemoCmdInfo, PacketHeader, UserCmd};
+pub use parse_game::{parse_game, MatchSummary, PeriodScore, StatsHandler};
+pub use player::{Connection, FlashStats, Player, ShotStats, WeaponStats};
+pub use playerinfo::PlayerInfo;
+pub use propdecode::PropValue;
+pub use reader::{DemoReader, Frame, FramePayload};
+pub use round::{MvpReason, Round, RoundEndReason, Side};
+pub use serverinfo::ServerInfo;
+pub use stringtables::{StringTable, StringTableEntry, StringTableSnapshot, StringTables};
+pub use weapon::{Weapon, WeaponCategory};
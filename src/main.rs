@@ -1,20 +1,56 @@
-mod bitreader;
-mod cmd;
-mod csgo;
-mod game_event;
-mod header;
-mod packet;
-mod parse_game;
-mod player;
-mod playerinfo;
 mod ranks;
 mod stable_hasher;
-mod stringtables;
 
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
 
+use log::info;
+
+use demoinfo::MatchSummary;
+
+fn print_stats(summary: &MatchSummary) {
+    info!("Score: {} - {}", summary.score.0, summary.score.1);
+    for period in &summary.periods {
+        info!(
+            "  {:?}: {} - {}",
+            period.period, period.score.0, period.score.1
+        );
+    }
+
+    for round in &summary.round_history {
+        info!(
+            "  Round {:2}: {:?} win, {:?} ({:.0}s)",
+            round.number, round.winner, round.reason, round.duration
+        );
+    }
+
+    for (i, team) in [&summary.winners, &summary.losers].iter().enumerate() {
+        info!("Team {}:", i + 1);
+        let mut players = team.iter().collect::<Vec<_>>();
+        players.sort_by_key(|player| (-player.kills, -player.assists, player.deaths));
+        for player in players {
+            info!(
+                "[{:2}]{:16}(k/a/d {:3} {:3} {:3} ({} f) HS: {:.0}% ADR: {:.0} KAST: {:.0}% accuracy: {:.0}%)",
+                player.info.user_id,
+                player.name,
+                player.kills,
+                player.assists,
+                player.deaths,
+                player.flash_assists,
+                player.headshot_percentage(),
+                player.adr(),
+                player.kast_percentage(),
+                player.shots().accuracy(),
+            );
+        }
+    }
+
+    if let Some(player) = summary.winners.first() {
+        info!("Winner team: team with {}", player.name);
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init_from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
@@ -26,8 +62,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut rankmanager = ranks::RankManager::new()?;
 
-    let (header, team_a, team_b) = parse_game::parse_game(reader)?;
-    rankmanager.update_ranks(&header, &team_a, &team_b)?;
+    let summary = demoinfo::parse_game(reader)?;
+    info!("Tickrate: {} ticks/second", summary.tickrate());
+    print_stats(&summary);
+    rankmanager.update_ranks(&summary.header, &summary.winners, &summary.losers)?;
 
     Ok(())
 }
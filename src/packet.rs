@@ -1,8 +1,9 @@
 use std::io::Read;
 
-use crate::bitreader::ReadExtras;
+use crate::bitreader::{string_from_nilslice, BitReader, ReadExtras};
+use crate::error::{DemoError, Result};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CmdType {
     SignOn = 1,
     Packet = 2,
@@ -15,6 +16,7 @@ pub enum CmdType {
     StringTables = 9,
 }
 
+/// Header that precedes every frame.
 #[derive(Clone, Copy, Debug)]
 pub struct PacketHeader {
     pub cmd_type: CmdType,
@@ -23,11 +25,11 @@ pub struct PacketHeader {
 }
 
 impl PacketHeader {
-    pub fn new<R: Read>(reader: &mut R) -> PacketHeader {
-        let cmd_type = reader.read_u8().unwrap();
-        let tick = reader.read_i32().unwrap();
-        let player_slot = reader.read_u8().unwrap();
-        PacketHeader {
+    pub fn new<R: Read>(reader: &mut R) -> Result<PacketHeader> {
+        let cmd_type = reader.read_u8()?;
+        let tick = reader.read_i32()?;
+        let player_slot = reader.read_u8()?;
+        Ok(PacketHeader {
             cmd_type: match cmd_type {
                 1 => CmdType::SignOn,
                 2 => CmdType::Packet,
@@ -38,61 +40,234 @@ impl PacketHeader {
                 7 => CmdType::Stop,
                 8 => CmdType::CustomData,
                 9 => CmdType::StringTables,
-                other => panic!("Unexpected command type: {}", other),
+                other => return Err(DemoError::UnknownCommand(other)),
             },
             tick,
             player_slot,
-        }
+        })
     }
 }
 
+/// View information of one split screen player, recorded with packet frames.
 #[derive(Clone, Copy, Debug)]
 pub struct DemoCmdInfo {
-    flags: i32,
+    pub flags: i32,
 
-    view_origin: (f32, f32, f32),
-    view_angle: (f32, f32, f32),
-    local_view_angles: (f32, f32, f32),
+    pub view_origin: (f32, f32, f32),
+    pub view_angle: (f32, f32, f32),
+    pub local_view_angles: (f32, f32, f32),
 
-    view_origin2: (f32, f32, f32),
-    view_angle2: (f32, f32, f32),
-    local_view_angles2: (f32, f32, f32),
+    pub view_origin2: (f32, f32, f32),
+    pub view_angle2: (f32, f32, f32),
+    pub local_view_angles2: (f32, f32, f32),
 }
 
 impl DemoCmdInfo {
-    pub fn new<R: Read>(r: &mut R) -> DemoCmdInfo {
-        DemoCmdInfo {
-            flags: r.read_i32().unwrap(),
-            view_origin: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-            ),
-            view_angle: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-            ),
-            local_view_angles: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-            ),
-            view_origin2: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-            ),
-            view_angle2: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
+    pub fn new<R: Read>(r: &mut R) -> Result<DemoCmdInfo> {
+        Ok(DemoCmdInfo {
+            flags: r.read_i32()?,
+            view_origin: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+            view_angle: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+            local_view_angles: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+            view_origin2: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+            view_angle2: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+            local_view_angles2: (r.read_f32()?, r.read_f32()?, r.read_f32()?),
+        })
+    }
+}
+
+/// Read a length-prefixed blob, the common payload format of most frames.
+pub fn read_raw_data<R: Read>(r: &mut R) -> Result<Vec<u8>> {
+    let size = r.read_i32()?;
+    if size < 0 {
+        return Err(DemoError::TruncatedFrame);
+    }
+    Ok(r.read_u8_vec(size as usize)?)
+}
+
+/// A console command recorded with `dem_consolecmd`.
+#[derive(Clone, Debug)]
+pub struct ConsoleCmd {
+    pub command: String,
+}
+
+impl ConsoleCmd {
+    pub fn new<R: Read>(r: &mut R) -> Result<ConsoleCmd> {
+        Ok(ConsoleCmd {
+            command: string_from_nilslice(&read_raw_data(r)?),
+        })
+    }
+}
+
+/// A user command recorded with `dem_usercmd`.
+///
+/// User commands are delta encoded against the previous command of the same
+/// player, so fields that did not change are `None`.
+#[derive(Clone, Debug, Default)]
+pub struct UserCmd {
+    pub outgoing_sequence: i32,
+    pub command_number: Option<u32>,
+    pub tick_count: Option<u32>,
+    pub view_angles: (Option<f32>, Option<f32>, Option<f32>),
+    pub aim_direction: (Option<f32>, Option<f32>, Option<f32>),
+    pub forward_move: Option<f32>,
+    pub side_move: Option<f32>,
+    pub up_move: Option<f32>,
+    pub buttons: Option<u32>,
+    pub impulse: Option<u8>,
+    pub weapon_select: Option<u32>,
+    pub weapon_subtype: Option<u32>,
+    pub mouse_dx: Option<i16>,
+    pub mouse_dy: Option<i16>,
+}
+
+const MAX_EDICT_BITS: u8 = 11;
+const WEAPON_SUBTYPE_BITS: u8 = 6;
+
+fn read_delta<R: Read, T>(
+    r: &mut BitReader<R>,
+    read: impl Fn(&mut BitReader<R>) -> std::io::Result<T>,
+) -> std::io::Result<Option<T>> {
+    if r.read_bit()? {
+        Ok(Some(read(r)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn read_float<R: Read>(r: &mut BitReader<R>) -> std::io::Result<f32> {
+    Ok(f32::from_bits(r.read_bits_u32(32)?))
+}
+
+impl UserCmd {
+    pub fn new<R: Read>(r: &mut R) -> Result<UserCmd> {
+        let outgoing_sequence = r.read_i32()?;
+        let data = read_raw_data(r)?;
+        let mut reader = BitReader::new(data.as_slice());
+        let r = &mut reader;
+
+        let mut cmd = UserCmd {
+            outgoing_sequence,
+            command_number: read_delta(r, |r| r.read_bits_u32(32))?,
+            tick_count: read_delta(r, |r| r.read_bits_u32(32))?,
+            view_angles: (
+                read_delta(r, read_float)?,
+                read_delta(r, read_float)?,
+                read_delta(r, read_float)?,
             ),
-            local_view_angles2: (
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
-                r.read_f32().unwrap(),
+            aim_direction: (
+                read_delta(r, read_float)?,
+                read_delta(r, read_float)?,
+                read_delta(r, read_float)?,
             ),
+            forward_move: read_delta(r, read_float)?,
+            side_move: read_delta(r, read_float)?,
+            up_move: read_delta(r, read_float)?,
+            buttons: read_delta(r, |r| r.read_bits_u32(32))?,
+            impulse: read_delta(r, |r| Ok(r.read_bits_u32(8)? as u8))?,
+            ..Default::default()
+        };
+        if r.read_bit()? {
+            cmd.weapon_select = Some(r.read_bits_u32(MAX_EDICT_BITS)?);
+            cmd.weapon_subtype = read_delta(r, |r| r.read_bits_u32(WEAPON_SUBTYPE_BITS))?;
         }
+        cmd.mouse_dx = read_delta(r, |r| Ok(r.read_bits_u32(16)? as i16))?;
+        cmd.mouse_dy = read_delta(r, |r| Ok(r.read_bits_u32(16)? as i16))?;
+
+        Ok(cmd)
+    }
+}
+
+/// Game specific data recorded with `dem_customdata`.
+#[derive(Clone, Debug)]
+pub struct CustomData {
+    pub callback_index: i32,
+    pub data: Vec<u8>,
+}
+
+impl CustomData {
+    pub fn new<R: Read>(r: &mut R) -> Result<CustomData> {
+        let callback_index = r.read_i32()?;
+        let data = read_raw_data(r)?;
+        Ok(CustomData {
+            callback_index,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitreader::BitWriter;
+    use crate::packet::*;
+
+    fn raw_data(data: &[u8]) -> Vec<u8> {
+        let mut buf = (data.len() as i32).to_le_bytes().to_vec();
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn console_cmd() {
+        let data = raw_data(b"say hello\0");
+        let cmd = ConsoleCmd::new(&mut data.as_slice()).unwrap();
+        assert_eq!("say hello", cmd.command);
+    }
+
+    #[test]
+    fn user_cmd() {
+        let mut data = 7i32.to_le_bytes().to_vec();
+        data.extend(raw_data(
+            &BitWriter::new()
+                .write_bit(true)
+                .write_bits(100, 32)
+                .write_bit(false)
+                .write_bit(true)
+                .write_bits(90f32.to_bits(), 32)
+                .write_bits(0, 5)
+                .write_bit(true)
+                .write_bits(450f32.to_bits(), 32)
+                .write_bits(0, 2)
+                .write_bit(true)
+                .write_bits(1, 32)
+                .write_bit(false)
+                .write_bit(true)
+                .write_bits(42, 11)
+                .write_bit(false)
+                .write_bit(true)
+                .write_bits(-5i16 as u16 as u32, 16)
+                .write_bit(false)
+                .finish(),
+        ));
+        let cmd = UserCmd::new(&mut data.as_slice()).unwrap();
+        assert_eq!(7, cmd.outgoing_sequence);
+        assert_eq!(Some(100), cmd.command_number);
+        assert_eq!(None, cmd.tick_count);
+        assert_eq!((Some(90.0), None, None), cmd.view_angles);
+        assert_eq!((None, None, None), cmd.aim_direction);
+        assert_eq!(Some(450.0), cmd.forward_move);
+        assert_eq!(None, cmd.side_move);
+        assert_eq!(Some(1), cmd.buttons);
+        assert_eq!(None, cmd.impulse);
+        assert_eq!(Some(42), cmd.weapon_select);
+        assert_eq!(None, cmd.weapon_subtype);
+        assert_eq!(Some(-5), cmd.mouse_dx);
+        assert_eq!(None, cmd.mouse_dy);
+    }
+
+    #[test]
+    fn custom_data() {
+        let mut data = 3i32.to_le_bytes().to_vec();
+        data.extend(raw_data(&[1, 2, 3]));
+        let custom = CustomData::new(&mut data.as_slice()).unwrap();
+        assert_eq!(3, custom.callback_index);
+        assert_eq!(vec![1, 2, 3], custom.data);
+    }
+
+    #[test]
+    fn truncated_raw_data() {
+        let data = raw_data(&[1, 2, 3]);
+        let err = read_raw_data(&mut &data[..5]).unwrap_err();
+        assert!(matches!(err, DemoError::TruncatedFrame));
     }
 }
@@ -1,21 +1,42 @@
-use prost::Message;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::io::Read;
 
-use log::{debug, info, trace, warn};
+use log::{debug, trace, warn};
 
-use crate::bitreader::*;
-use crate::cmd::Cmd;
-use crate::csgo::netmessages_public::csvc_msg_game_event::KeyT;
-use crate::csgo::*;
-use crate::game_event::{Event, EventContext};
+use crate::bomb::{BombRound, BombSite};
+use crate::convars::{MatchRules, Period};
+use crate::demo::{parse_demo, Demo};
+use crate::economy::{EconomyRound, PlayerEconomy};
+use crate::error::Result;
+use crate::game_event::{Event, HitGroup, Kill};
+use crate::grenade::{Blind, Grenade, Grenades};
+use crate::handler::DemoHandler;
 use crate::header::Header;
-use crate::packet::{CmdType, DemoCmdInfo, PacketHeader};
-use crate::player::Player;
-use crate::stringtables::{create_string_table, update_string_table, StringTable};
+use crate::player::{Connection, Player};
+use crate::playerinfo::PlayerInfo;
+use crate::round::{Round, RoundEndReason, Side};
+use crate::serverinfo::ServerInfo;
+use crate::weapon::{Weapon, WeaponCategory};
 
 const TRADE_TIME_LIMIT_IN_SECONDS: f32 = 18.2;
+const MAX_HEALTH: i32 = 100;
+/// Longest time after a flash that a kill of the blinded player is credited
+/// to the flash.
+const FLASH_KILL_TIME_IN_SECONDS: f32 = 3.0;
+/// Longest pause between shots that are part of the same spray.
+const SPRAY_INTERVAL_IN_SECONDS: f32 = 0.25;
+
+/// Shots a player fired in quick succession with one weapon.
+#[derive(Clone, Debug)]
+struct Burst {
+    weapon: Weapon,
+    round: i32,
+    last_tick: i32,
+    shots: i32,
+    hits: i32,
+    last_shot_hit: bool,
+}
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum PlayerState {
@@ -26,184 +47,269 @@ enum PlayerState {
     Died(i32, i32),
 }
 
-#[derive(Clone, Debug)]
-struct State {
+/// Built-in handler that keeps score and K/A/D/KAST per player.
+#[derive(Clone, Debug, Default)]
+pub struct StatsHandler {
     header: Header,
-    player_table: Option<StringTable>,
-    table_id: usize,
+    tickrate: i32,
+    rules: MatchRules,
     current_tick: i32,
     current_round: i32,
+    /// Score of the team that started on T and the team that started on CT.
     score: (i32, i32),
+    /// Score of each period, in the same order as `score`.
+    periods: Vec<(Period, (i32, i32))>,
 
-    events: EventContext,
+    /// Players by the first user id they had.
     players: HashMap<i32, Player>,
+    /// Every user id seen, mapped to the key of its player in `players`.
+    user_ids: HashMap<i32, i32>,
+    /// Ticks of `player_connect` events whose userinfo has not arrived yet.
+    pending_connects: HashMap<i32, i32>,
     current_round_player_state: HashMap<i32, PlayerState>,
+    /// Whether each player is on the team that started on T.
     teams: HashMap<i32, bool>,
+    /// Health of each player, to cap damage at what the victim had left.
+    health: HashMap<i32, i32>,
+    /// The spray or tap each player fired last.
+    bursts: HashMap<i32, Burst>,
+
+    round_start_tick: i32,
+    /// Sites of the bomb targets by entity index.
+    bomb_sites: HashMap<i32, BombSite>,
+    bomb_round: BombRound,
+    bomb_rounds: Vec<BombRound>,
+    /// Player defusing the bomb, and whether they have a kit.
+    defuser: Option<(i32, bool)>,
+    grenades: Grenades,
+    /// Last enemy flash of each player this round, as (flasher, tick, duration).
+    blinds: HashMap<i32, (i32, i32, f32)>,
+
+    /// Players who died this round.
+    dead: HashSet<i32>,
+    /// Clan names of the teams on each side.
+    team_names: HashMap<Side, String>,
+    round_history: Vec<Round>,
+
+    /// Money of each player this round.
+    economy: HashMap<i32, PlayerEconomy>,
+    economy_rounds: Vec<EconomyRound>,
 }
 
-#[rustfmt::skip]
-#[allow(dead_code)]
-pub fn show_key(k: &KeyT) -> String {
-    match k {
-        KeyT { val_string: Some(s), .. } => format!("[string] {}", s),
-        KeyT { val_float: Some(n), .. } => format!("[float] {}", n),
-        KeyT { val_long: Some(n), .. } => format!("[long] {}", n),
-        KeyT { val_short: Some(n), .. } => format!("[short] {}", n),
-        KeyT { val_byte: Some(n), .. } => format!("[byte] {}", n),
-        KeyT { val_bool: Some(n), .. } => format!("[bool] {}", n),
-        KeyT { val_uint64: Some(n), .. } => format!("[u64] {}", n),
-        KeyT { val_wstring: Some(n), .. } => format!("[wstring] {}", String::from_utf8_lossy(&n).to_string()),
-        KeyT {
-            val_string: None,
-            val_float: None,
-            val_long: None,
-            val_short: None,
-            val_byte: None,
-            val_bool: None,
-            val_uint64: None,
-            val_wstring: None,
-            ..
-        } => "[empty]".to_string()
-    }
+fn as_seconds<N>(ticks: N, tickrate: i32) -> f32
+where
+    i64: TryFrom<N>,
+{
+    (i64::try_from(ticks).ok().unwrap() as f32) / tickrate as f32
 }
 
-fn handle_user_message(msg: netmessages_public::CsvcMsgUserMessage) {
-    let data: &[u8] = &msg.msg_data.unwrap();
-    let cmd = msg.msg_type.unwrap();
+impl DemoHandler for StatsHandler {
+    fn on_header(&mut self, header: &Header) {
+        self.header = header.clone();
+        self.tickrate = header.tickrate();
+    }
+
+    fn on_convar(&mut self, demo: &Demo, _name: &str, _value: &str) {
+        self.rules = demo.rules();
+    }
 
-    // See: protos/cstrike15_usermessages_public.proto::ECstrike15UserMessages
-    if cmd == 6 {
-        let _msg = cstrike15_usermessages_public::CcsUsrMsgSayText2::decode(data).unwrap();
-        //dbg!(msg);
+    fn on_server_info(&mut self, demo: &Demo, _info: &ServerInfo) {
+        self.tickrate = demo.tickrate();
     }
-}
 
-fn read_event_names(
-    list: netmessages_public::CsvcMsgGameEventList,
-) -> HashMap<i32, (String, HashMap<usize, String>)> {
-    let mut result = HashMap::new();
+    fn on_tick(&mut self, _demo: &Demo, tick: i32) {
+        self.current_tick = tick;
+    }
 
-    for event in list.descriptors {
-        if let (Some(id), Some(name)) = (event.eventid, event.name) {
-            let inner: HashMap<usize, String> = event
-                .keys
-                .into_iter()
-                .enumerate()
-                .map(|(i, key)| {
-                    if let Some(name) = key.name {
-                        Some((i, name))
-                    } else {
-                        None
-                    }
-                })
-                .flatten()
-                .collect();
-            result.insert(id, (name, inner));
+    fn on_event(&mut self, demo: &Demo, event: &Event) {
+        if let Event::BombBeginPlant { userid, site } | Event::BombPlanted { userid, site } = event
+        {
+            if let Some(bomb_site) = demo.bomb_site(*userid) {
+                self.bomb_sites.entry(*site).or_insert(bomb_site);
+            }
         }
+        if let Event::RoundEnd { .. } = event {
+            for side in [Side::T, Side::CT] {
+                let name = demo.team_name(side).unwrap_or("").to_string();
+                self.team_names.insert(side, name);
+            }
+            for (id, user_id) in self.connected_players() {
+                let economy = self.player_economy(id);
+                economy.remaining_money = demo.money(user_id).unwrap_or(0);
+                economy.money_spent = demo.money_spent(user_id).unwrap_or(0);
+            }
+        }
+        if let Event::RoundFreezeEnd = event {
+            for (id, user_id) in self.connected_players() {
+                let economy = self.player_economy(id);
+                economy.start_money = demo.round_start_money(user_id).unwrap_or(0);
+                economy.equipment_value = demo.equipment_value(user_id).unwrap_or(0);
+            }
+        }
+        self.handle_game_event(event);
     }
 
-    result
+    fn on_player_info(&mut self, _demo: &Demo, info: &PlayerInfo) {
+        self.update_player_info(info.clone());
+    }
 }
 
-fn as_seconds<N>(ticks: N, tickrate: i32) -> f32
-where
-    i64: TryFrom<N>,
-{
-    (i64::try_from(ticks).ok().unwrap() as f32) / tickrate as f32
-}
+impl StatsHandler {
+    pub fn new() -> StatsHandler {
+        StatsHandler::default()
+    }
 
-impl State {
-    pub fn new(header: Header) -> State {
-        State {
-            header,
-            player_table: None,
-            table_id: 0,
-            current_tick: 0,
-            current_round: 0,
-            score: (0, 0),
+    /// Find an earlier record of the same person: by xuid, or by name for bots.
+    fn find_player(&self, info: &PlayerInfo) -> Option<i32> {
+        self.players
+            .iter()
+            .find(|(_, p)| {
+                if info.xuid != 0 {
+                    p.info.xuid == info.xuid
+                } else {
+                    info.fake && p.info.fake && p.info.name == info.name
+                }
+            })
+            .map(|(id, _)| *id)
+    }
 
-            events: EventContext::new(HashMap::new()),
-            players: HashMap::new(),
-            current_round_player_state: HashMap::new(),
-            teams: HashMap::new(),
-        }
+    /// Key in `players` of the player with the given user id.
+    fn player_id(&self, user_id: i32) -> i32 {
+        self.user_ids.get(&user_id).copied().unwrap_or(user_id)
     }
 
-    fn find_player_by_xuid(&self, xuid: i64) -> Option<i32> {
+    /// All players seen in the demo, one record per person.
+    pub fn players(&self) -> impl Iterator<Item = &Player> {
+        self.players.values()
+    }
+
+    pub fn player_by_user_id(&self, user_id: i32) -> Option<&Player> {
+        self.players.get(&self.player_id(user_id))
+    }
+
+    /// Connected players as (player id, current user id).
+    fn connected_players(&self) -> Vec<(i32, i32)> {
         self.players
             .iter()
-            .filter_map(|(i, p)| if p.info.xuid == xuid { Some(*i) } else { None })
-            .next()
+            .filter(|(_, p)| p.is_connected())
+            .filter_map(|(id, p)| Some((*id, p.connections.last()?.user_id)))
+            .collect()
+    }
+
+    fn player_economy(&mut self, id: i32) -> &mut PlayerEconomy {
+        self.economy.entry(id).or_insert_with(|| PlayerEconomy {
+            player: id,
+            ..PlayerEconomy::default()
+        })
     }
 
     pub fn current_time(&self) -> String {
-        let second = self.current_tick / self.header.tickrate();
+        let second = self.current_tick / self.tickrate;
 
         format!("{}m {}s", second / 60, second % 60)
     }
 
-    pub fn handle_command(&mut self, cmd: Cmd) -> std::io::Result<()> {
-        match cmd {
-            Cmd::CreateStringTable(table) => {
-                if let Some((table, players)) = create_string_table(table)? {
-                    self.player_table = Some(table);
-                    for (i, info) in players {
-                        self.players.insert(i, Player::new(info));
-                    }
-                } else if self.player_table.is_none() {
-                    self.table_id += 1;
-                }
+    fn update_player_info(&mut self, info: PlayerInfo) {
+        let user_id = info.user_id;
+        if let Some(id) = self.user_ids.get(&user_id) {
+            if let Some(player) = self.players.get_mut(id) {
+                player.set_info(info);
             }
-            Cmd::UpdateStringTable(table) => {
-                if table.table_id == Some(self.table_id as i32) {
-                    for (i, info) in
-                        update_string_table(self.player_table.as_mut().unwrap(), table)?
-                    {
-                        if let Some(p) = self.find_player_by_xuid(info.xuid) {
-                            let mut player = self.players.remove(&p).unwrap();
-                            player.info = info;
-                            self.players.insert(i, player);
-                        } else {
-                            self.players.insert(i, Player::new(info));
-                        }
-                    }
-                }
-            }
-            Cmd::UserMessage(message) => {
-                handle_user_message(message);
+            return;
+        }
+
+        let connection = Connection {
+            user_id,
+            connect_tick: self
+                .pending_connects
+                .remove(&user_id)
+                .unwrap_or(self.current_tick),
+            disconnect_tick: None,
+        };
+        let id = match self.find_player(&info) {
+            Some(id) => {
+                debug!("{} reconnected as user id {}", info.name, user_id);
+                self.players.get_mut(&id).unwrap().set_info(info);
+                id
             }
-            Cmd::GameEvent(event) => {
-                self.handle_game_event(event);
+            None => {
+                self.players.insert(user_id, Player::new(info));
+                user_id
             }
-            Cmd::GameEventList(event_list) => {
-                self.events = EventContext::new(read_event_names(event_list));
+        };
+        self.players
+            .get_mut(&id)
+            .unwrap()
+            .connections
+            .push(connection);
+        self.user_ids.insert(user_id, id);
+    }
+
+    fn handle_connect(&mut self, user_id: i32) {
+        if !self.user_ids.contains_key(&user_id) {
+            self.pending_connects.insert(user_id, self.current_tick);
+        }
+    }
+
+    fn handle_disconnect(&mut self, user_id: i32, reason: &str) {
+        let tick = self.current_tick;
+        let player = self.players.get_mut(&self.player_id(user_id));
+        let connection = player.and_then(|p| {
+            p.connections
+                .iter_mut()
+                .rev()
+                .find(|c| c.user_id == user_id && c.disconnect_tick.is_none())
+        });
+        match connection {
+            Some(connection) => {
+                debug!("User id {} disconnected: {}", user_id, reason);
+                connection.disconnect_tick = Some(tick);
             }
+            None => warn!("Did not find player who disconnected with id {}", user_id),
         }
-        Ok(())
+    }
+
+    /// The round being played, or about to start, counting from 1.
+    fn round(&self) -> i32 {
+        self.score.0 + self.score.1 + 1
     }
 
     fn handle_round_start(&mut self) {
-        self.current_round += 1;
+        // round_announce_match_start and round_start both start the first round
+        if self.current_round == self.round() {
+            return;
+        }
+        self.current_round = self.round();
 
         debug!("--\nRound {}\n{}", self.current_round, self.current_time());
-        if self.current_round == 16 {
+        if self.rules.swaps_sides_before(self.current_round) {
             debug!("Swapping sides");
-            self.score = (self.score.1, self.score.0);
         }
+        self.finish_bursts();
+        self.clear_kast();
+        self.round_start_tick = self.current_tick;
+        self.bomb_round = BombRound::new(self.current_round);
+        self.defuser = None;
+        self.blinds.clear();
+        self.dead.clear();
+        self.economy.clear();
     }
 
+    /// Count the KAST of the round that just ended. Does nothing if it was
+    /// already counted.
     fn update_player_kast_score(&mut self) {
-        debug!("{} {:?}", 50, &self.current_round_player_state[&50]);
-        debug!("{} {:?}", 56, &self.current_round_player_state[&56]);
-        for (i, state) in &self.current_round_player_state {
-            match state {
-                PlayerState::Killed
-                | PlayerState::Assisted
-                | PlayerState::Survived
-                | PlayerState::Traded => {
-                    self.players.get_mut(&i).unwrap().kast += 1;
+        for (i, state) in std::mem::take(&mut self.current_round_player_state) {
+            if let Some(player) = self.players.get_mut(&i) {
+                player.rounds += 1;
+                match state {
+                    PlayerState::Killed
+                    | PlayerState::Assisted
+                    | PlayerState::Survived
+                    | PlayerState::Traded => {
+                        player.kast += 1;
+                    }
+                    PlayerState::Died(_, _) => {}
                 }
-                PlayerState::Died(_, _) => {}
             }
         }
     }
@@ -211,53 +317,199 @@ impl State {
     fn clear_kast(&mut self) {
         self.current_round_player_state = self
             .players
-            .keys()
-            .map(|k| (*k, PlayerState::Survived))
+            .iter()
+            .filter(|(_, p)| p.is_connected())
+            .map(|(k, _)| (*k, PlayerState::Survived))
             .collect();
     }
 
-    fn handle_game_event(&mut self, ev: netmessages_public::CsvcMsgGameEvent) {
-        match self.events.parse_game_event(ev) {
+    fn handle_round_end(&mut self, winner: Side, reason: RoundEndReason, message: &str) {
+        let round = self.round();
+        let swapped = self.rules.sides_swapped(round);
+        let first_t_won = (winner == Side::T) != swapped;
+        let period = self.rules.period(round);
+        if self.periods.last().map(|(p, _)| *p) != Some(period) {
+            self.periods.push((period, (0, 0)));
+        }
+        let period_score = &mut self.periods.last_mut().unwrap().1;
+        if first_t_won {
+            self.score.0 += 1;
+            period_score.0 += 1;
+        } else {
+            self.score.1 += 1;
+            period_score.1 += 1;
+        }
+        debug!("{:?} win: {:?}", winner, reason);
+
+        let mut survivors = (0, 0);
+        for (id, first_t) in &self.teams {
+            let alive =
+                !self.dead.contains(id) && self.players.get(id).is_some_and(|p| p.is_connected());
+            match (alive, *first_t != swapped) {
+                (true, true) => survivors.0 += 1,
+                (true, false) => survivors.1 += 1,
+                (false, _) => {}
+            }
+        }
+        let team_name = |side| self.team_names.get(&side).cloned().unwrap_or_default();
+        self.round_history.push(Round {
+            number: round,
+            winner,
+            t_name: team_name(Side::T),
+            ct_name: team_name(Side::CT),
+            reason,
+            message: message.to_string(),
+            mvp: None,
+            mvp_reason: None,
+            duration: self.as_seconds(self.current_tick - self.round_start_tick),
+            t_survivors: survivors.0,
+            ct_survivors: survivors.1,
+        });
+
+        if let Some((defuser, _)) = self.defuser.take() {
+            self.abort_defuse(defuser);
+        }
+        let bomb_round = std::mem::replace(&mut self.bomb_round, BombRound::new(round + 1));
+        self.bomb_rounds.push(BombRound {
+            round,
+            ..bomb_round
+        });
+
+        let teams = &self.teams;
+        let mut players = self
+            .economy
+            .drain()
+            .map(|(id, economy)| PlayerEconomy {
+                side: teams.get(&id).map(|first_t| {
+                    if *first_t != swapped {
+                        Side::T
+                    } else {
+                        Side::CT
+                    }
+                }),
+                ..economy
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|p| p.player);
+        self.economy_rounds.push(EconomyRound { round, players });
+
+        // round_officially_ended is not sent after the last round
+        if self.rules.is_match_over(self.score) {
+            self.update_player_kast_score();
+        }
+        debug!("Score: {:?}", self.score);
+    }
+
+    fn handle_game_event(&mut self, event: &Event) {
+        match event {
             Event::Filtered => {}
             Event::BeginNewMatch => self.clear_stats(),
             Event::RoundStart => self.handle_round_start(),
-            Event::RoundOfficiallyEnded => {
-                self.update_player_kast_score();
-                self.clear_kast();
-            }
-            Event::RoundEnd(winner) => {
-                if winner {
-                    debug!("T win");
-                    self.score.0 += 1;
-                } else {
-                    debug!("CT win");
-                    self.score.1 += 1;
+            Event::RoundFreezeEnd => trace!("Freeze time over {}", self.current_time()),
+            Event::RoundOfficiallyEnded => self.update_player_kast_score(),
+            Event::RoundEnd {
+                winner,
+                reason,
+                message,
+            } => self.handle_round_end(*winner, *reason, message),
+            Event::RoundMvp { userid, reason } => {
+                let id = self.player_id(*userid);
+                if let Some(player) = self.players.get_mut(&id) {
+                    player.mvps += 1;
                 }
-                if self.score.0 == 16 || self.score.1 == 16 {
-                    self.update_player_kast_score();
+                if let Some(round) = self.round_history.last_mut() {
+                    round.mvp = Some(id);
+                    round.mvp_reason = Some(*reason);
                 }
-                debug!("Score: {:?}", self.score);
             }
             Event::ItemEquip(userid, item) => {
-                self.equip(userid, item);
+                self.equip(self.player_id(*userid), item.clone());
             }
-            Event::PlayerSpawn(userid, team) => {
-                self.teams.insert(userid, team);
+            Event::ItemPurchase(userid, item) => {
+                let id = self.player_id(*userid);
+                self.player_economy(id).items.push(item.clone());
             }
-            Event::PlayerDeath {
-                victim,
-                killer,
-                assist,
-                flash_assist,
-                weapon,
-            } => {
+            Event::PlayerSpawn(userid, is_t) => {
+                let first_t = *is_t != self.rules.sides_swapped(self.round());
+                self.teams.insert(self.player_id(*userid), first_t);
+                self.health.insert(self.player_id(*userid), MAX_HEALTH);
+            }
+            Event::PlayerConnect(userid) => self.handle_connect(*userid),
+            Event::PlayerDisconnect(userid, reason) => self.handle_disconnect(*userid, reason),
+            Event::PlayerDeath(kill) => {
+                let victim = self.player_id(kill.victim);
                 if let Some((muna, tick)) = self.muna_in_hand(victim) {
                     debug!(
                         "{}, (muna in hand = {}, age = {:.1}s)",
                         self.players[&victim].name, muna, tick
                     );
                 }
-                self.update_stats(victim, killer, assist, flash_assist, &weapon);
+                self.dead.insert(victim);
+                self.credit_flash(kill.victim, kill.killer);
+                self.update_stats(kill);
+            }
+            Event::PlayerHurt {
+                victim,
+                attacker,
+                health,
+                weapon,
+                dmg_health,
+                hitgroup,
+                ..
+            } => self.handle_damage(*victim, *attacker, *health, weapon, *dmg_health, *hitgroup),
+            Event::WeaponFire(userid, weapon) => self.handle_weapon_fire(*userid, weapon),
+            Event::BombPlanted { userid, site } => self.handle_bomb_planted(*userid, *site),
+            Event::BombBeginDefuse { userid, has_kit } => {
+                let id = self.player_id(*userid);
+                if let Some((defuser, _)) = self.defuser.replace((id, *has_kit)) {
+                    self.abort_defuse(defuser);
+                }
+            }
+            Event::BombAbortDefuse(userid) => {
+                let id = self.player_id(*userid);
+                if self.defuser.map(|(defuser, _)| defuser) == Some(id) {
+                    self.defuser = None;
+                    self.abort_defuse(id);
+                }
+            }
+            Event::BombDefused { userid, site } => self.handle_bomb_defused(*userid, *site),
+            Event::BombExploded { .. } => {
+                self.bomb_round.exploded = true;
+                if let Some((defuser, _)) = self.defuser.take() {
+                    self.abort_defuse(defuser);
+                }
+            }
+            Event::GrenadeDetonate {
+                userid,
+                kind,
+                entityid,
+                position,
+            } => {
+                let grenade = self.grenades.detonate(
+                    self.player_id(*userid),
+                    *kind,
+                    self.current_round,
+                    self.current_tick,
+                    *entityid,
+                    *position,
+                );
+                trace!("{:?}", grenade);
+            }
+            Event::InfernoStartBurn { entityid, position } => {
+                self.grenades
+                    .start_fire(*entityid, self.current_round, *position)
+            }
+            Event::PlayerBlind {
+                userid,
+                attacker,
+                entityid,
+                duration,
+            } => self.handle_blind(*userid, *attacker, *entityid, *duration),
+            Event::SmokeExpired(entityid) | Event::InfernoExpire(entityid) => {
+                self.grenades.expire(*entityid, self.current_tick)
+            }
+            Event::BombBeginPlant { .. } | Event::BombDropped(_) | Event::BombPickup(_) => {
+                trace!("{:?} {}", event, self.current_time());
             }
             Event::Other(name) => {
                 trace!("{} {}", name, self.current_time());
@@ -269,8 +521,20 @@ impl State {
         debug!("------\n\n");
         self.current_round = 0;
         self.score = (0, 0);
+        self.periods.clear();
+        self.current_round_player_state.clear();
+        self.bursts.clear();
+        self.bomb_round = BombRound::new(1);
+        self.bomb_rounds.clear();
+        self.defuser = None;
+        self.grenades.clear();
+        self.blinds.clear();
+        self.dead.clear();
+        self.round_history.clear();
+        self.economy.clear();
+        self.economy_rounds.clear();
         for (_, player) in self.players.iter_mut() {
-            *player = Player::new(player.info.clone());
+            player.reset_stats();
         }
     }
 
@@ -299,30 +563,33 @@ impl State {
             );
         }
 
+        let players = &self.players;
+        let name = |id: &i32| players.get(id).map(|p| p.name.as_str()).unwrap_or("?");
+        let killer = killer.unwrap_or(0);
         for (traded_id, state) in self.current_round_player_state.iter_mut() {
             if let PlayerState::Died(maybe_traded_killer, tick) = *state {
-                let trade_time = as_seconds(self.current_tick - tick, self.header.tickrate());
+                let trade_time = as_seconds(self.current_tick - tick, self.tickrate);
                 if maybe_traded_killer == victim && trade_time < TRADE_TIME_LIMIT_IN_SECONDS {
                     debug!(
                         "[{}]{} traded [{}]{} by killing [{}]{} ({}s)",
-                        killer.unwrap(),
-                        &self.players[&killer.unwrap()].name,
+                        killer,
+                        name(&killer),
                         traded_id,
-                        &self.players[&traded_id].name,
+                        name(traded_id),
                         victim,
-                        &self.players[&victim].name,
+                        name(&victim),
                         trade_time
                     );
                     *state = PlayerState::Traded;
                 } else if maybe_traded_killer == victim {
                     debug!(
                         "[{}]{} was too late to trade [{}]{} by killing [{}]{} ({}s)",
-                        killer.unwrap(),
-                        &self.players[&killer.unwrap()].name,
+                        killer,
+                        name(&killer),
                         traded_id,
-                        &self.players[&traded_id].name,
+                        name(traded_id),
                         victim,
-                        &self.players[&victim].name,
+                        name(&victim),
                         trade_time
                     );
                 }
@@ -330,14 +597,12 @@ impl State {
         }
     }
 
-    pub fn update_stats(
-        &mut self,
-        death: i32,
-        killer: Option<i32>,
-        assist: Option<i32>,
-        assist_flash: bool,
-        _weapon: &str,
-    ) -> Option<()> {
+    pub fn update_stats(&mut self, details: &Kill) -> Option<()> {
+        let death = self.player_id(details.victim);
+        let killer = details.killer.map(|id| self.player_id(id));
+        let assist = details.assist.map(|id| self.player_id(id));
+        let assist_flash = details.flash_assist;
+
         self.update_kast(killer, assist, death);
         let kill = killer.unwrap_or(death);
 
@@ -349,6 +614,14 @@ impl State {
             // self.players.get_mut(&kill).unwrap().kills -= 1;
         } else if let Some(killer) = self.players.get_mut(&kill) {
             killer.kills += 1;
+            let weapon = killer.weapons.entry(details.weapon.clone()).or_default();
+            weapon.kills += 1;
+            weapon.headshot_kills += details.headshot as i32;
+            killer.headshot_kills += details.headshot as i32;
+            killer.wallbang_kills += details.wallbang() as i32;
+            killer.noscope_kills += details.noscope as i32;
+            killer.smoke_kills += details.through_smoke as i32;
+            killer.blind_kills += details.attacker_blind as i32;
         } else {
             warn!("Did not find player who killed with id {}", kill);
         }
@@ -375,12 +648,266 @@ impl State {
 
         if let Some(victim) = self.players.get_mut(&death) {
             victim.deaths += 1;
+            victim
+                .weapons
+                .entry(details.weapon.clone())
+                .or_default()
+                .deaths += 1;
         } else {
             warn!("Did not find player who died with id {}", death);
         }
         Some(())
     }
 
+    fn handle_damage(
+        &mut self,
+        victim: i32,
+        attacker: Option<i32>,
+        health: i32,
+        weapon: &Weapon,
+        dmg_health: i32,
+        hitgroup: HitGroup,
+    ) {
+        let victim = self.player_id(victim);
+        let health_before = self.health.insert(victim, health).unwrap_or(MAX_HEALTH);
+        let attacker = match attacker.map(|id| self.player_id(id)) {
+            Some(attacker) if attacker != victim => attacker,
+            _ => return,
+        };
+        self.register_hit(attacker, weapon, hitgroup);
+        if let (Some(a), Some(v)) = (self.teams.get(&attacker), self.teams.get(&victim)) {
+            if a == v {
+                return;
+            }
+        }
+
+        let damage = dmg_health.min(health_before).max(0);
+        if let Some(player) = self.players.get_mut(&attacker) {
+            player.damage += damage;
+            *player.damage_by_hitgroup.entry(hitgroup).or_default() += damage;
+            player.weapons.entry(weapon.clone()).or_default().damage += damage;
+            if weapon.category() == WeaponCategory::Grenade {
+                player.utility_damage += damage;
+            }
+        } else {
+            warn!("Did not find player who did damage with id {}", attacker);
+        }
+    }
+
+    fn handle_blind(
+        &mut self,
+        victim: i32,
+        attacker: Option<i32>,
+        entity_id: Option<i32>,
+        duration: f32,
+    ) {
+        let victim = self.player_id(victim);
+        let tick = self.current_tick;
+        if let Some(entity_id) = entity_id {
+            let blind = Blind {
+                victim,
+                tick,
+                duration,
+            };
+            self.grenades.blind(entity_id, blind);
+        }
+
+        let attacker = match attacker {
+            Some(attacker) => self.player_id(attacker),
+            None => return,
+        };
+        let attacker_team = self.teams.get(&attacker);
+        let teammates = attacker_team.is_some() && attacker_team == self.teams.get(&victim);
+        let flashes = match self.players.get_mut(&attacker) {
+            Some(player) => &mut player.flashes,
+            None => {
+                warn!("Did not find player who flashed with id {}", attacker);
+                return;
+            }
+        };
+        if attacker == victim {
+            flashes.self_flashes += 1;
+        } else if teammates {
+            flashes.teammates_flashed += 1;
+        } else {
+            flashes.enemies_flashed += 1;
+            flashes.enemy_blind_ms += (duration * 1000.0).round() as i32;
+            self.blinds.insert(victim, (attacker, tick, duration));
+        }
+    }
+
+    /// Credit a kill to the flash that blinded the victim, if they were still
+    /// blind and the flash was recent.
+    fn credit_flash(&mut self, victim: i32, killer: Option<i32>) {
+        let victim = self.player_id(victim);
+        let killer = match killer {
+            Some(killer) => self.player_id(killer),
+            None => return,
+        };
+        let (flasher, tick, duration) = match self.blinds.remove(&victim) {
+            Some(blind) => blind,
+            None => return,
+        };
+        let elapsed = self.as_seconds(self.current_tick - tick);
+        let same_team = matches!(
+            (self.teams.get(&killer), self.teams.get(&flasher)),
+            (Some(a), Some(b)) if a == b
+        );
+        if same_team && elapsed <= duration.min(FLASH_KILL_TIME_IN_SECONDS) {
+            if let Some(player) = self.players.get_mut(&flasher) {
+                player.flashes.flash_kills += 1;
+            }
+        }
+    }
+
+    fn handle_bomb_planted(&mut self, user_id: i32, site: i32) {
+        let id = self.player_id(user_id);
+        if let Some(player) = self.players.get_mut(&id) {
+            player.plants += 1;
+        } else {
+            warn!("Did not find player who planted with id {}", id);
+        }
+        self.bomb_round.planter = Some(id);
+        self.bomb_round.site = self.bomb_sites.get(&site).copied();
+        self.bomb_round.plant_time =
+            Some(self.as_seconds(self.current_tick - self.round_start_tick));
+    }
+
+    fn handle_bomb_defused(&mut self, user_id: i32, site: i32) {
+        let id = self.player_id(user_id);
+        let has_kit = match self.defuser.take() {
+            Some((defuser, has_kit)) if defuser == id => has_kit,
+            _ => false,
+        };
+        if let Some(player) = self.players.get_mut(&id) {
+            player.defuses += 1;
+        } else {
+            warn!("Did not find player who defused with id {}", id);
+        }
+        let defuse_time = self.as_seconds(self.current_tick - self.round_start_tick);
+        let site = self.bomb_sites.get(&site).copied();
+        let round = &mut self.bomb_round;
+        round.defuser = Some(id);
+        round.defuse_kit = has_kit;
+        round.site = round.site.or(site);
+        round.defuse_time = Some(defuse_time);
+    }
+
+    fn abort_defuse(&mut self, id: i32) {
+        if let Some(player) = self.players.get_mut(&id) {
+            player.aborted_defuses += 1;
+        }
+        self.bomb_round.aborted_defuses += 1;
+    }
+
+    fn handle_weapon_fire(&mut self, user_id: i32, weapon: &Weapon) {
+        let id = self.player_id(user_id);
+        if let Some(kind) = weapon.grenade_type() {
+            self.grenades
+                .throw(id, kind, self.current_round, self.current_tick);
+            if let Some(player) = self.players.get_mut(&id) {
+                *player.grenades_thrown.entry(kind).or_default() += 1;
+            }
+            return;
+        }
+        if !weapon.category().is_gun() {
+            return;
+        }
+        let tick = self.current_tick;
+        let round = self.current_round;
+        let tickrate = self.tickrate;
+        match self.bursts.get_mut(&id) {
+            Some(burst)
+                if burst.weapon == *weapon
+                    && as_seconds(tick - burst.last_tick, tickrate)
+                        <= SPRAY_INTERVAL_IN_SECONDS =>
+            {
+                burst.shots += 1;
+                burst.last_tick = tick;
+                burst.last_shot_hit = false;
+            }
+            _ => {
+                let burst = Burst {
+                    weapon: weapon.clone(),
+                    round,
+                    last_tick: tick,
+                    shots: 1,
+                    hits: 0,
+                    last_shot_hit: false,
+                };
+                if let Some(burst) = self.bursts.insert(id, burst) {
+                    self.finish_burst(id, burst);
+                }
+            }
+        }
+
+        if let Some(player) = self.players.get_mut(&id) {
+            player
+                .weapons
+                .entry(weapon.clone())
+                .or_default()
+                .shots
+                .shots += 1;
+            player.shots_by_round.entry(round).or_default().shots += 1;
+        } else {
+            warn!("Did not find player who fired with id {}", id);
+        }
+    }
+
+    /// Count a hit for the last shot of the attacker, if it was fired with
+    /// the weapon and did not hit anyone yet.
+    fn register_hit(&mut self, attacker: i32, weapon: &Weapon, hitgroup: HitGroup) {
+        let burst = match self.bursts.get_mut(&attacker) {
+            Some(burst) if burst.weapon == *weapon && !burst.last_shot_hit => burst,
+            _ => return,
+        };
+        burst.hits += 1;
+        burst.last_shot_hit = true;
+
+        if let Some(player) = self.players.get_mut(&attacker) {
+            let headshot = (hitgroup == HitGroup::Head) as i32;
+            for stats in [
+                &mut player
+                    .weapons
+                    .entry(burst.weapon.clone())
+                    .or_default()
+                    .shots,
+                player.shots_by_round.entry(burst.round).or_default(),
+            ] {
+                stats.hits += 1;
+                stats.headshot_hits += headshot;
+            }
+        }
+    }
+
+    /// Count the shots of a finished burst as a tap or a spray.
+    fn finish_burst(&mut self, id: i32, burst: Burst) {
+        if let Some(player) = self.players.get_mut(&id) {
+            for stats in [
+                &mut player
+                    .weapons
+                    .entry(burst.weapon.clone())
+                    .or_default()
+                    .shots,
+                player.shots_by_round.entry(burst.round).or_default(),
+            ] {
+                if burst.shots == 1 {
+                    stats.taps += 1;
+                    stats.tap_hits += burst.hits;
+                } else {
+                    stats.spray_shots += burst.shots;
+                    stats.spray_hits += burst.hits;
+                }
+            }
+        }
+    }
+
+    fn finish_bursts(&mut self) {
+        for (id, burst) in std::mem::take(&mut self.bursts) {
+            self.finish_burst(id, burst);
+        }
+    }
+
     fn equip(&mut self, id: i32, item: String) {
         let munas = [
             "hegrenade",
@@ -390,16 +917,19 @@ impl State {
             "molotov",
         ];
 
-        let mut player = self.players.get_mut(&id).unwrap();
-        if munas.contains(&item.as_str()) {
-            player.latest_muna = Some(item.clone());
-            player.muna_tick = self.current_tick;
+        if let Some(player) = self.players.get_mut(&id) {
+            if munas.contains(&item.as_str()) {
+                player.latest_muna = Some(item.clone());
+                player.muna_tick = self.current_tick;
+            }
+            player.equipped = item;
+        } else {
+            warn!("Did not find player who equipped with id {}", id);
         }
-        player.equipped = item;
     }
 
     fn as_seconds(&self, ticks: i32) -> f32 {
-        as_seconds(ticks, self.header.tickrate())
+        as_seconds(ticks, self.tickrate)
     }
 
     fn muna_in_hand(&self, id: i32) -> Option<(String, f32)> {
@@ -413,123 +943,127 @@ impl State {
             })
             .filter(|(_, time)| time < &2.5)
     }
+}
 
-    pub fn print_stats(&self) {
-        info!("Score: {} - {}", self.score.0, self.score.1);
+/// Result of parsing a whole demo.
+#[derive(Clone, Debug)]
+pub struct MatchSummary {
+    pub header: Header,
+    pub server_info: Option<ServerInfo>,
+    /// Final score as (winners, losers).
+    pub score: (i32, i32),
+    /// Number of rounds played.
+    pub rounds: i32,
+    /// Score of each half and overtime half.
+    pub periods: Vec<PeriodScore>,
+    pub winners: Vec<Player>,
+    pub losers: Vec<Player>,
+    /// Every round played, in order.
+    pub round_history: Vec<Round>,
+    /// What happened with the bomb, one record per round.
+    pub bomb_rounds: Vec<BombRound>,
+    /// Every grenade thrown, in the order they were thrown.
+    pub grenades: Vec<Grenade>,
+    /// Purchases and money of every player, one record per round.
+    pub economy: Vec<EconomyRound>,
+}
 
-        let mut current_team = None;
-        let mut player_list = self
-            .players
-            .iter()
-            .filter(|(id, _)| self.teams.contains_key(id))
-            .map(|(id, player)| (self.teams[id], player))
-            .collect::<Vec<_>>();
-        player_list
-            .sort_by_key(|(team, player)| (*team, -player.kills, -player.assists, player.deaths));
-        for (team, player) in player_list {
-            if current_team != Some(team) {
-                current_team = Some(team);
-                info!("Team {}:", if !team { 1 } else { 2 });
-            }
-            info!(
-                "[{:2}]{:16}(k/a/d {:3} {:3} {:3} ({} f) KAST: {:.0}%)",
-                self.find_player_by_xuid(player.info.xuid).unwrap(),
-                player.name,
-                player.kills,
-                player.assists,
-                player.deaths,
-                player.flash_assists,
-                100.0 * (player.kast as f32) / ((self.score.0 + self.score.1) as f32),
-            );
-        }
+/// Score of one period as (winners, losers), like [`MatchSummary::score`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeriodScore {
+    pub period: Period,
+    pub score: (i32, i32),
+}
+
+impl MatchSummary {
+    /// Ticks per second, from the server info if the demo had one.
+    pub fn tickrate(&self) -> i32 {
+        self.server_info
+            .as_ref()
+            .and_then(|info| info.tickrate())
+            .unwrap_or_else(|| self.header.tickrate())
     }
 }
 
-pub fn parse_game<R: Read>(
-    mut reader: R,
-) -> Result<(Header, Vec<Player>, Vec<Player>), std::io::Error> {
-    let header = Header::new(&mut reader);
-    info!("Tickrate: {} ticks/second", header.tickrate());
+impl StatsHandler {
+    /// Build the match summary once the whole demo has been parsed.
+    pub fn into_summary(mut self, demo: Demo) -> MatchSummary {
+        self.finish_bursts();
+        let mut team_a = self
+            .players
+            .iter()
+            .filter(|(id, _)| self.teams.get(id) == Some(&true))
+            .map(|(_, p)| p)
+            .cloned()
+            .collect::<Vec<_>>();
 
-    let mut state = State::new(header.clone());
+        let mut team_b = self
+            .players
+            .iter()
+            .filter(|(id, _)| self.teams.get(id) == Some(&false))
+            .map(|(_, p)| p)
+            .cloned()
+            .collect::<Vec<_>>();
 
-    loop {
-        let header = PacketHeader::new(&mut reader);
-        state.current_tick = header.tick;
-        match header.cmd_type {
-            CmdType::SyncTick => {
-                continue;
-            }
-            CmdType::Stop => {
-                break;
-            }
-            CmdType::SignOn | CmdType::Packet => {
-                let _split1 = DemoCmdInfo::new(&mut reader);
-                let _split2 = DemoCmdInfo::new(&mut reader);
+        team_a.sort_by_key(|p| p.info.xuid);
+        team_b.sort_by_key(|p| p.info.xuid);
 
-                reader.read_u32().unwrap();
-                reader.read_u32().unwrap();
+        let (score_a, score_b) = self.score;
+        let first_t_won = score_a > score_b;
+        let (winners, losers, score) = if first_t_won {
+            (team_a, team_b, (score_a, score_b))
+        } else {
+            (team_b, team_a, (score_b, score_a))
+        };
+        let periods = self
+            .periods
+            .iter()
+            .map(|(period, (a, b))| PeriodScore {
+                period: *period,
+                score: if first_t_won { (*a, *b) } else { (*b, *a) },
+            })
+            .collect();
 
-                let size: u32 = reader.read_u32().unwrap();
-                let slice = reader.read_u8_vec(size as usize).unwrap();
-                let mut read = (*slice).as_ref();
-                while let Some(cmd) = Cmd::parse(&mut read) {
-                    state.handle_command(cmd)?;
-                }
-            }
-            CmdType::ConsoleCmd => unimplemented!(),
-            CmdType::UserCmd => unimplemented!(),
-            CmdType::DataTables => {
-                let size: u32 = reader.read_u32().unwrap();
-                let _slice = reader.read_u8_vec(size as usize).unwrap();
-            }
-            CmdType::CustomData => unimplemented!(),
-            CmdType::StringTables => unimplemented!(),
-            //cmd => {
-            //}
+        MatchSummary {
+            header: demo.header().clone(),
+            server_info: demo.server_info().cloned(),
+            score,
+            rounds: score_a + score_b,
+            periods,
+            winners,
+            losers,
+            round_history: self.round_history,
+            bomb_rounds: self.bomb_rounds,
+            grenades: self.grenades.into_vec(),
+            economy: self.economy_rounds,
         }
     }
+}
 
-    state.print_stats();
-
-    let mut team_a = state
-        .players
-        .iter()
-        .filter(|(id, _)| state.teams.get(id) == Some(&true))
-        .map(|(_, p)| p)
-        .cloned()
-        .collect::<Vec<_>>();
-
-    let mut team_b = state
-        .players
-        .iter()
-        .filter(|(id, _)| state.teams.get(id) == Some(&false))
-        .map(|(_, p)| p)
-        .cloned()
-        .collect::<Vec<_>>();
-
-    team_a.sort_by_key(|p| p.info.xuid);
-    team_b.sort_by_key(|p| p.info.xuid);
-
-    if state.score.0 > state.score.1 {
-        info!("Winner team: team with {}", team_a[0].name);
-        Ok((header, team_a, team_b))
-    } else {
-        info!("Winner team: team with {}", team_b[0].name);
-        Ok((header, team_b, team_a))
-    }
+pub fn parse_game<R: Read>(reader: R) -> Result<MatchSummary> {
+    let mut stats = StatsHandler::new();
+    let demo = parse_demo(reader, &mut stats)?;
+    Ok(stats.into_summary(demo))
 }
 
 #[cfg(test)]
 mod test {
     use crate::bitreader::*;
+    use crate::cmd::Cmd;
+    use crate::csgo::netmessages_public::CsvcMsgPacketEntities;
+    use crate::datatables::test::{encode, prop, table};
+    use crate::datatables::{SendProp, DPT_DATA_TABLE, DPT_INT, SPROP_UNSIGNED};
+    use crate::demo::test::{add_player, apply, demo_with_data_tables};
+    use crate::error::DemoError;
+    use crate::grenade::GrenadeType;
     use crate::parse_game::*;
-    use crate::playerinfo::PlayerInfo;
+    use crate::round::MvpReason;
 
     #[test]
+    #[allow(clippy::excessive_precision)]
     fn header_parse() {
         let data = include_bytes!("example_header");
-        let header = Header::new(&mut data.as_ref());
+        let header = Header::new(&mut data.as_ref()).unwrap();
         assert_eq!("HL2DEMO", header.magic);
         assert_eq!(4, header.demo_protocol);
         assert_eq!(13769, header.network_protocol);
@@ -543,43 +1077,95 @@ mod test {
         assert_eq!(447407, header.signon_length);
     }
 
+    #[test]
+    fn bad_magic() {
+        let mut data = include_bytes!("example_header").to_vec();
+        data[0] = b'X';
+        let err = Header::new(&mut data.as_slice()).unwrap_err();
+        assert!(matches!(err, DemoError::BadMagic(magic) if magic == "XL2DEMO"));
+    }
+
+    #[test]
+    fn truncated_header() {
+        let mut data: &[u8] = &include_bytes!("example_header")[..100];
+        let err = Header::new(&mut data).unwrap_err();
+        assert!(matches!(err, DemoError::TruncatedFrame));
+    }
+
+    #[test]
+    fn error_location() {
+        let mut data = include_bytes!("example_header").to_vec();
+        let header_len = data.len() as u64;
+        data.extend_from_slice(&[42, 1, 0, 0, 0, 0]);
+        let err = parse_game(data.as_slice()).unwrap_err();
+        assert!(matches!(err.kind(), DemoError::UnknownCommand(42)));
+        assert_eq!(Some(header_len), err.offset());
+    }
+
     #[test]
     fn eof() {
         let data = &[];
-        Cmd::parse(&mut data.as_ref());
+        assert!(Cmd::parse(&mut data.as_ref()).unwrap().is_none());
     }
 
     #[test]
     fn read_var() {
-        assert_eq!(None, (&mut (&[]).as_ref()).read_var_u32().ok());
-        assert_eq!(Some(1), (&mut (&[1]).as_ref()).read_var_u32().ok());
-        assert_eq!(None, (&mut (&[255]).as_ref()).read_var_u32().ok());
-        assert_eq!(Some(4), (&mut (&[4]).as_ref()).read_var_u32().ok());
-        assert_eq!(Some(2226), (&mut (&[178, 17]).as_ref()).read_var_u32().ok());
+        assert_eq!(None, (&mut [].as_ref()).read_var_u32().ok());
+        assert_eq!(Some(1), (&mut [1].as_ref()).read_var_u32().ok());
+        assert_eq!(None, (&mut [255].as_ref()).read_var_u32().ok());
+        assert_eq!(Some(4), (&mut [4].as_ref()).read_var_u32().ok());
+        assert_eq!(Some(2226), (&mut [178, 17].as_ref()).read_var_u32().ok());
     }
 
-    fn gen_player(state: &mut State, id: i32, team: bool) -> i32 {
-        let info = PlayerInfo {
+    fn player_info(id: i32, xuid: i64, name: &str, fake: bool) -> PlayerInfo {
+        PlayerInfo {
             version: 0,
-            xuid: 0,
-            name: format!("Player {}", id),
+            xuid,
+            name: name.to_string(),
             user_id: id,
             guid: format!("STEAMGUID-{}", id),
             friends_id: 0,
             friends_name: format!("FriendsName{}", id),
-            fake: false,
+            fake,
             proxy: false,
             custom_files_crc: [0, 0, 0, 0],
             files_downloaded: 0,
             entity_id: 0,
-        };
-        state.players.insert(id, Player::new(info));
-        state.teams.insert(id, team);
+        }
+    }
+
+    fn gen_player(state: &mut StatsHandler, id: i32, team: bool) -> i32 {
+        state.update_player_info(player_info(id, 0, &format!("Player {}", id), false));
+        state.handle_game_event(&Event::PlayerSpawn(id, team));
         id
     }
 
-    fn stat(state: &State, id: i32) -> (i32, i32, i32, i32) {
-        let player = &state.players[&id];
+    fn kill(victim: i32, killer: Option<i32>, assist: Option<i32>, flash_assist: bool) -> Kill {
+        Kill {
+            victim,
+            killer,
+            assist,
+            flash_assist,
+            weapon: Weapon::Ak47,
+            ..Kill::default()
+        }
+    }
+
+    fn round_end(t_won: bool) -> Event {
+        let (winner, reason) = if t_won {
+            (Side::T, RoundEndReason::TerroristsWin)
+        } else {
+            (Side::CT, RoundEndReason::CtWin)
+        };
+        Event::RoundEnd {
+            winner,
+            reason,
+            message: String::new(),
+        }
+    }
+
+    fn stat(state: &StatsHandler, id: i32) -> (i32, i32, i32, i32) {
+        let player = state.player_by_user_id(id).unwrap();
         (
             player.kills,
             player.assists,
@@ -590,47 +1176,757 @@ mod test {
 
     #[test]
     fn kills() {
-        let mut state = State::new(Header {
+        let mut state = StatsHandler::new();
+        state.on_header(&Header {
             playback_time: 1.0,
             playback_ticks: 1,
             ..Default::default()
         });
-        let killer = Some(gen_player(&mut state, 1, false));
+        let killer = gen_player(&mut state, 1, false);
         let victim = gen_player(&mut state, 2, true);
         let assister = gen_player(&mut state, 3, false);
         let friendly_assister = gen_player(&mut state, 4, true);
 
-        assert_eq!(stat(&state, killer.unwrap()), (0, 0, 0, 0));
+        assert_eq!(stat(&state, killer), (0, 0, 0, 0));
         assert_eq!(stat(&state, victim), (0, 0, 0, 0));
         assert_eq!(stat(&state, assister), (0, 0, 0, 0));
         assert_eq!(stat(&state, friendly_assister), (0, 0, 0, 0));
 
-        state.update_stats(victim, killer, None, false, "knife");
+        state.update_stats(&kill(victim, Some(killer), None, false));
 
-        assert_eq!(stat(&state, killer.unwrap()), (1, 0, 0, 0));
+        assert_eq!(stat(&state, killer), (1, 0, 0, 0));
         assert_eq!(stat(&state, victim), (0, 0, 1, 0));
         assert_eq!(stat(&state, assister), (0, 0, 0, 0));
         assert_eq!(stat(&state, friendly_assister), (0, 0, 0, 0));
 
-        state.update_stats(victim, killer, Some(assister), false, "knife");
+        state.update_stats(&kill(victim, Some(killer), Some(assister), false));
 
-        assert_eq!(stat(&state, killer.unwrap()), (2, 0, 0, 0));
+        assert_eq!(stat(&state, killer), (2, 0, 0, 0));
         assert_eq!(stat(&state, victim), (0, 0, 2, 0));
         assert_eq!(stat(&state, assister), (0, 1, 0, 0));
         assert_eq!(stat(&state, friendly_assister), (0, 0, 0, 0));
 
-        state.update_stats(victim, killer, Some(assister), true, "knife");
+        state.update_stats(&kill(victim, Some(killer), Some(assister), true));
 
-        assert_eq!(stat(&state, killer.unwrap()), (3, 0, 0, 0));
+        assert_eq!(stat(&state, killer), (3, 0, 0, 0));
         assert_eq!(stat(&state, victim), (0, 0, 3, 0));
         assert_eq!(stat(&state, assister), (0, 1, 0, 1));
         assert_eq!(stat(&state, friendly_assister), (0, 0, 0, 0));
 
-        state.update_stats(victim, killer, Some(friendly_assister), false, "knife");
+        state.update_stats(&kill(victim, Some(killer), Some(friendly_assister), false));
 
-        assert_eq!(stat(&state, killer.unwrap()), (4, 0, 0, 0));
+        assert_eq!(stat(&state, killer), (4, 0, 0, 0));
         assert_eq!(stat(&state, victim), (0, 0, 4, 0));
         assert_eq!(stat(&state, assister), (0, 1, 0, 1));
         assert_eq!(stat(&state, friendly_assister), (0, -1, 0, 0));
     }
+
+    fn hurt(
+        victim: i32,
+        attacker: i32,
+        health: i32,
+        weapon: &str,
+        dmg: i32,
+        hitgroup: i32,
+    ) -> Event {
+        Event::PlayerHurt {
+            victim,
+            attacker: Some(attacker),
+            health,
+            weapon: Weapon::from_name(weapon),
+            dmg_health: dmg,
+            dmg_armor: 0,
+            hitgroup: HitGroup::from(hitgroup),
+        }
+    }
+
+    #[test]
+    fn damage() {
+        let mut state = StatsHandler::new();
+        let a = gen_player(&mut state, 1, true);
+        let b = gen_player(&mut state, 2, false);
+        let teammate = gen_player(&mut state, 3, true);
+
+        state.handle_game_event(&Event::RoundStart);
+        state.handle_game_event(&hurt(b, a, 73, "ak47", 27, 2));
+        // Overkill only counts the health that was left
+        state.handle_game_event(&hurt(b, a, 0, "awp", 448, 1));
+        // Team damage and self damage do not count
+        state.handle_game_event(&hurt(teammate, a, 50, "ak47", 50, 3));
+        state.handle_game_event(&hurt(a, a, 90, "hegrenade", 10, 0));
+        state.handle_game_event(&round_end(true));
+        state.handle_game_event(&Event::RoundOfficiallyEnded);
+
+        state.handle_game_event(&Event::RoundStart);
+        state.handle_game_event(&Event::PlayerSpawn(b, false));
+        state.handle_game_event(&hurt(b, a, 60, "hegrenade", 40, 0));
+        state.handle_game_event(&hurt(b, a, 0, "inferno", 80, 0));
+        state.handle_game_event(&round_end(true));
+        state.handle_game_event(&Event::RoundOfficiallyEnded);
+
+        let player = state.player_by_user_id(a).unwrap();
+        assert_eq!(200, player.damage);
+        assert_eq!(100, player.utility_damage);
+        assert_eq!(100.0, player.adr());
+        assert_eq!(
+            vec![
+                (HitGroup::Generic, 100),
+                (HitGroup::Head, 73),
+                (HitGroup::Chest, 27)
+            ],
+            player
+                .damage_by_hitgroup
+                .clone()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, state.player_by_user_id(teammate).unwrap().damage);
+    }
+
+    #[test]
+    fn shots() {
+        let mut state = StatsHandler::new();
+        state.tickrate = 64;
+        let a = gen_player(&mut state, 1, true);
+        let b = gen_player(&mut state, 2, false);
+        let fire = |state: &mut StatsHandler, tick: i32, weapon: &str| {
+            state.current_tick = tick;
+            state.handle_game_event(&Event::WeaponFire(a, Weapon::from_name(weapon)));
+        };
+
+        state.handle_game_event(&Event::RoundStart);
+        // A tap to the head
+        fire(&mut state, 0, "deagle");
+        state.handle_game_event(&hurt(b, a, 0, "deagle", 120, 1));
+        // A spray of three with one hit, then a tap that misses
+        fire(&mut state, 640, "ak47");
+        fire(&mut state, 646, "ak47");
+        state.handle_game_event(&hurt(b, a, 73, "ak47", 27, 2));
+        fire(&mut state, 652, "ak47");
+        fire(&mut state, 700, "ak47");
+        // Knife swings and grenades are not shots
+        fire(&mut state, 800, "knife_t");
+        fire(&mut state, 900, "hegrenade");
+        state.handle_game_event(&hurt(b, a, 50, "hegrenade", 23, 0));
+        state.handle_game_event(&round_end(true));
+        state.handle_game_event(&Event::RoundOfficiallyEnded);
+
+        state.handle_game_event(&Event::RoundStart);
+        fire(&mut state, 2000, "ak47");
+        // Two hits from one shot count once
+        state.handle_game_event(&hurt(b, a, 70, "ak47", 30, 3));
+        state.handle_game_event(&hurt(b, a, 40, "ak47", 30, 3));
+        state.finish_bursts();
+
+        let player = state.player_by_user_id(a).unwrap();
+        let deagle = player.weapons[&Weapon::Deagle].shots;
+        assert_eq!((1, 1, 1), (deagle.shots, deagle.hits, deagle.headshot_hits));
+        let ak = player.weapons[&Weapon::Ak47].shots;
+        assert_eq!((5, 2, 0), (ak.shots, ak.hits, ak.headshot_hits));
+        assert_eq!((2, 1), (ak.taps, ak.tap_hits));
+        assert_eq!((3, 1), (ak.spray_shots, ak.spray_hits));
+        assert_eq!(0, player.weapons[&Weapon::HeGrenade].shots.shots);
+        assert!(!player.weapons.contains_key(&Weapon::Knife));
+        assert_eq!(1, player.grenades_thrown[&GrenadeType::HighExplosive]);
+
+        let total = player.shots();
+        assert_eq!(6, total.shots);
+        assert_eq!(50.0, total.accuracy());
+        assert_eq!(100.0 / 3.0, total.headshot_percentage());
+        assert_eq!(5, player.shots_by_round[&1].shots);
+        let round = player.shots_by_round[&2];
+        assert_eq!((1, 1), (round.shots, round.hits));
+    }
+
+    #[test]
+    fn bomb() {
+        let mut state = StatsHandler::new();
+        state.tickrate = 64;
+        let t = gen_player(&mut state, 1, true);
+        let ct = gen_player(&mut state, 2, false);
+        let kit_ct = gen_player(&mut state, 3, false);
+        state.bomb_sites.insert(301, BombSite::A);
+        let planted = |userid, site| Event::BombPlanted { userid, site };
+        let defusing = |userid, has_kit| Event::BombBeginDefuse { userid, has_kit };
+        let events = vec![
+            (640, Event::RoundStart),
+            (3200, planted(t, 301)),
+            (3300, defusing(ct, false)),
+            (3400, defusing(kit_ct, true)),
+            (
+                3720,
+                Event::BombDefused {
+                    userid: kit_ct,
+                    site: 301,
+                },
+            ),
+            (3720, round_end(false)),
+            (4000, Event::RoundStart),
+            (5000, planted(t, 302)),
+            (5100, defusing(ct, false)),
+            (5200, Event::BombAbortDefuse(ct)),
+            (5300, defusing(ct, false)),
+            (
+                7560,
+                Event::BombExploded {
+                    userid: t,
+                    site: 302,
+                },
+            ),
+            (7560, round_end(true)),
+        ];
+        for (tick, event) in events {
+            state.current_tick = tick;
+            state.handle_game_event(&event);
+        }
+
+        assert_eq!(
+            vec![
+                BombRound {
+                    round: 1,
+                    site: Some(BombSite::A),
+                    planter: Some(t),
+                    plant_time: Some(40.0),
+                    defuser: Some(kit_ct),
+                    defuse_time: Some(48.125),
+                    defuse_kit: true,
+                    aborted_defuses: 1,
+                    exploded: false,
+                },
+                BombRound {
+                    round: 2,
+                    site: None,
+                    planter: Some(t),
+                    plant_time: Some(15.625),
+                    aborted_defuses: 2,
+                    exploded: true,
+                    ..BombRound::default()
+                },
+            ],
+            state.bomb_rounds
+        );
+        let player = |id| state.player_by_user_id(id).unwrap();
+        assert_eq!(2, player(t).plants);
+        assert_eq!((0, 3), (player(ct).defuses, player(ct).aborted_defuses));
+        assert_eq!(
+            (1, 0),
+            (player(kit_ct).defuses, player(kit_ct).aborted_defuses)
+        );
+    }
+
+    #[test]
+    fn flashes() {
+        let mut state = StatsHandler::new();
+        state.tickrate = 64;
+        let flasher = gen_player(&mut state, 1, true);
+        let teammate = gen_player(&mut state, 2, true);
+        let enemy = gen_player(&mut state, 3, false);
+        let other_enemy = gen_player(&mut state, 4, false);
+        let blind = |userid, duration| Event::PlayerBlind {
+            userid,
+            attacker: Some(flasher),
+            entityid: Some(100),
+            duration,
+        };
+        let death = |victim| Event::PlayerDeath(kill(victim, Some(teammate), None, false));
+        let events = vec![
+            (0, Event::RoundStart),
+            (100, Event::WeaponFire(flasher, Weapon::Flashbang)),
+            (200, blind(enemy, 2.5)),
+            (200, blind(other_enemy, 1.0)),
+            (200, blind(teammate, 3.0)),
+            (200, blind(flasher, 0.5)),
+            (
+                200,
+                Event::GrenadeDetonate {
+                    userid: flasher,
+                    kind: GrenadeType::Flashbang,
+                    entityid: Some(100),
+                    position: [0.0; 3],
+                },
+            ),
+            // Still blind, so the flash gets the credit
+            (300, death(enemy)),
+            // No longer blind
+            (300, death(other_enemy)),
+        ];
+        for (tick, event) in events {
+            state.current_tick = tick;
+            state.handle_game_event(&event);
+        }
+
+        let flashes = state.player_by_user_id(flasher).unwrap().flashes;
+        assert_eq!(2, flashes.enemies_flashed);
+        assert_eq!(1, flashes.teammates_flashed);
+        assert_eq!(1, flashes.self_flashes);
+        assert_eq!(3.5, flashes.enemy_blind_time());
+        assert_eq!(1.75, flashes.average_blind_time());
+        assert_eq!(1, flashes.flash_kills);
+
+        let grenades = state.grenades.into_vec();
+        assert_eq!(Some(100), grenades[0].throw_tick);
+        assert_eq!(4, grenades[0].blinded.len());
+    }
+
+    #[test]
+    fn flash_kill_needs_known_teams() {
+        let mut state = StatsHandler::new();
+        state.tickrate = 64;
+        // Neither player has spawned, so their teams are unknown
+        for id in 1..=3 {
+            state.update_player_info(player_info(id, 0, &format!("Player {}", id), false));
+        }
+        let events = vec![
+            (0, Event::RoundStart),
+            (
+                100,
+                Event::PlayerBlind {
+                    userid: 3,
+                    attacker: Some(1),
+                    entityid: Some(100),
+                    duration: 2.5,
+                },
+            ),
+            (150, Event::PlayerDeath(kill(3, Some(2), None, false))),
+        ];
+        for (tick, event) in events {
+            state.current_tick = tick;
+            state.handle_game_event(&event);
+        }
+
+        let flashes = state.player_by_user_id(1).unwrap().flashes;
+        assert_eq!(0, flashes.flash_kills);
+    }
+
+    #[test]
+    fn round_history() {
+        let mut state = StatsHandler::new();
+        state.tickrate = 64;
+        let t = gen_player(&mut state, 1, true);
+        let other_t = gen_player(&mut state, 2, true);
+        let ct = gen_player(&mut state, 3, false);
+        state.team_names.insert(Side::T, "Tigers".to_string());
+        state.team_names.insert(Side::CT, "Cats".to_string());
+
+        let events = vec![
+            (640, Event::RoundStart),
+            (
+                2000,
+                Event::PlayerDeath(kill(other_t, Some(ct), None, false)),
+            ),
+            (
+                8000,
+                Event::RoundEnd {
+                    winner: Side::CT,
+                    reason: RoundEndReason::BombDefused,
+                    message: "#SFUI_Notice_Bomb_Defused".to_string(),
+                },
+            ),
+            (
+                8000,
+                Event::RoundMvp {
+                    userid: ct,
+                    reason: MvpReason::BombDefused,
+                },
+            ),
+            (8500, Event::RoundOfficiallyEnded),
+            (9000, Event::RoundStart),
+            (10000, round_end(true)),
+        ];
+        for (tick, event) in events {
+            state.current_tick = tick;
+            state.handle_game_event(&event);
+        }
+
+        assert_eq!(
+            Round {
+                number: 1,
+                winner: Side::CT,
+                t_name: "Tigers".to_string(),
+                ct_name: "Cats".to_string(),
+                reason: RoundEndReason::BombDefused,
+                message: "#SFUI_Notice_Bomb_Defused".to_string(),
+                mvp: Some(ct),
+                mvp_reason: Some(MvpReason::BombDefused),
+                duration: 115.0,
+                t_survivors: 1,
+                ct_survivors: 1,
+            },
+            state.round_history[0]
+        );
+        let second = &state.round_history[1];
+        assert_eq!(
+            (2, Side::T, None),
+            (second.number, second.winner, second.mvp)
+        );
+        assert_eq!((2, 1), (second.t_survivors, second.ct_survivors));
+        assert_eq!(1, state.player_by_user_id(ct).unwrap().mvps);
+        assert_eq!(0, state.player_by_user_id(t).unwrap().mvps);
+    }
+
+    #[test]
+    fn kill_details() {
+        let mut state = StatsHandler::new();
+        let killer = gen_player(&mut state, 1, true);
+        let victim = gen_player(&mut state, 2, false);
+        let teammate = gen_player(&mut state, 3, true);
+
+        state.update_stats(&Kill {
+            headshot: true,
+            penetrated: 1,
+            through_smoke: true,
+            ..kill(victim, Some(killer), None, false)
+        });
+        state.update_stats(&Kill {
+            noscope: true,
+            attacker_blind: true,
+            ..kill(victim, Some(killer), None, false)
+        });
+        // Team kills do not count
+        state.update_stats(&Kill {
+            headshot: true,
+            ..kill(teammate, Some(killer), None, false)
+        });
+
+        let player = state.player_by_user_id(killer).unwrap();
+        assert_eq!(2, player.kills);
+        assert_eq!(1, player.headshot_kills);
+        assert_eq!(50.0, player.headshot_percentage());
+        assert_eq!(1, player.wallbang_kills);
+        assert_eq!(1, player.noscope_kills);
+        assert_eq!(1, player.smoke_kills);
+        assert_eq!(1, player.blind_kills);
+    }
+
+    #[test]
+    fn economy() {
+        let mut state = StatsHandler::new();
+        let t = gen_player(&mut state, 1, true);
+        let ct = gen_player(&mut state, 2, false);
+        let buy = |state: &mut StatsHandler, userid, item| {
+            state.handle_game_event(&Event::ItemPurchase(userid, Weapon::from_name(item)))
+        };
+
+        state.handle_game_event(&Event::RoundStart);
+        buy(&mut state, t, "weapon_tec9");
+        buy(&mut state, t, "item_kevlar");
+        buy(&mut state, ct, "item_defuser");
+        // Money is read from the player entities by on_event
+        for (id, start_money, equipment_value) in [(t, 4000, 1850), (ct, 3000, 600)] {
+            let economy = state.player_economy(id);
+            economy.start_money = start_money;
+            economy.equipment_value = equipment_value;
+        }
+        state.handle_game_event(&Event::RoundFreezeEnd);
+        for (id, money_spent, remaining_money) in [(t, 1650, 2350), (ct, 400, 2900)] {
+            let economy = state.player_economy(id);
+            economy.money_spent = money_spent;
+            economy.remaining_money = remaining_money;
+        }
+        state.handle_game_event(&round_end(true));
+        state.handle_game_event(&Event::RoundOfficiallyEnded);
+
+        state.handle_game_event(&Event::RoundStart);
+        buy(&mut state, t, "weapon_ak47");
+        state.handle_game_event(&round_end(true));
+
+        let first = &state.economy_rounds[0];
+        assert_eq!(
+            PlayerEconomy {
+                player: t,
+                side: Some(Side::T),
+                start_money: 4000,
+                money_spent: 1650,
+                items: vec![Weapon::Tec9, Weapon::Kevlar],
+                equipment_value: 1850,
+                remaining_money: 2350,
+            },
+            first.players[0]
+        );
+        assert_eq!(vec![Weapon::DefuseKit], first.players[1].items);
+        let ct_total = first.team(Side::CT);
+        assert_eq!(
+            (3000, 400, 1),
+            (ct_total.start_money, ct_total.money_spent, ct_total.items)
+        );
+
+        let second = &state.economy_rounds[1];
+        assert_eq!(2, second.round);
+        assert_eq!(1, second.players.len());
+        assert_eq!(vec![Weapon::Ak47], second.players[0].items);
+    }
+
+    /// Create entities, as (id, class id, values of all props), where every
+    /// prop is a 16 bit unsigned int and the server has two classes.
+    fn packet_entities(entities: &[(i32, u32, &[u32])]) -> Cmd {
+        let mut w = BitWriter::new();
+        let mut last_id = -1;
+        for (id, class_id, values) in entities {
+            w.write_bits((id - last_id - 1) as u32, 6);
+            last_id = *id;
+            w.write_bit(false).write_bit(true);
+            w.write_bits(*class_id, 2).write_bits(0, 10);
+            w.write_bit(true);
+            for _ in values.iter() {
+                w.write_bit(true);
+            }
+            w.write_bit(false).write_bit(false);
+            w.write_bits(127, 7).write_bits(127, 7);
+            for value in values.iter() {
+                w.write_bits(*value, 16);
+            }
+        }
+        Cmd::PacketEntities(CsvcMsgPacketEntities {
+            updated_entries: Some(entities.len() as i32),
+            is_delta: Some(false),
+            entity_data: Some(w.finish()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn economy_from_entities() {
+        let int = |name| prop(name, DPT_INT, SPROP_UNSIGNED, 16);
+        let data_tables = encode(
+            &[
+                table(
+                    "DT_CSPlayer",
+                    vec![
+                        int("m_iAccount"),
+                        int("m_iStartAccount"),
+                        int("m_unCurrentEquipmentValue"),
+                    ],
+                ),
+                table(
+                    "m_iCashSpentThisRound",
+                    vec![int("000"), int("001"), int("002")],
+                ),
+                table(
+                    "DT_CSPlayerResource",
+                    vec![SendProp {
+                        dt_name: Some("m_iCashSpentThisRound".to_string()),
+                        ..prop("m_iCashSpentThisRound", DPT_DATA_TABLE, 0, 0)
+                    }],
+                ),
+            ],
+            &[
+                (0, "CCSPlayer", "DT_CSPlayer"),
+                (1, "CCSPlayerResource", "DT_CSPlayerResource"),
+            ],
+        );
+        let mut demo = demo_with_data_tables(&data_tables);
+        let mut state = StatsHandler::new();
+        // Players 1 and 2 are entities 1 and 2
+        for user_id in [1, 2] {
+            let info = PlayerInfo {
+                entity_id: user_id as i64 - 1,
+                ..player_info(user_id, 0, &format!("Player {}", user_id), false)
+            };
+            add_player(&mut demo, info, &mut state);
+        }
+        state.on_event(&demo, &Event::PlayerSpawn(1, true));
+        state.on_event(&demo, &Event::PlayerSpawn(2, false));
+        state.on_event(&demo, &Event::RoundStart);
+        state.on_event(&demo, &Event::ItemPurchase(1, Weapon::Ak47));
+
+        apply(
+            &mut demo,
+            packet_entities(&[
+                (1, 0, &[1300, 4000, 2900]),
+                (2, 0, &[3000, 3000, 200]),
+                (3, 1, &[0, 2700, 0]),
+            ]),
+            &mut state,
+        );
+        state.on_event(&demo, &Event::RoundFreezeEnd);
+
+        apply(
+            &mut demo,
+            packet_entities(&[
+                (1, 0, &[4550, 4000, 2900]),
+                (2, 0, &[2600, 3000, 600]),
+                (3, 1, &[0, 2700, 400]),
+            ]),
+            &mut state,
+        );
+        state.on_event(&demo, &round_end(true));
+
+        let round = &state.economy_rounds[0];
+        assert_eq!(
+            PlayerEconomy {
+                player: 1,
+                side: Some(Side::T),
+                start_money: 4000,
+                money_spent: 2700,
+                items: vec![Weapon::Ak47],
+                equipment_value: 2900,
+                remaining_money: 4550,
+            },
+            round.players[0]
+        );
+        let ct = &round.players[1];
+        assert_eq!(
+            (3000, 400, 200, 2600),
+            (
+                ct.start_money,
+                ct.money_spent,
+                ct.equipment_value,
+                ct.remaining_money
+            )
+        );
+    }
+
+    #[test]
+    fn weapons() {
+        let mut state = StatsHandler::new();
+        let a = gen_player(&mut state, 1, true);
+        let b = gen_player(&mut state, 2, false);
+
+        for _ in 0..2 {
+            state.handle_game_event(&Event::RoundStart);
+            state.handle_game_event(&Event::PlayerSpawn(b, false));
+            state.handle_game_event(&Event::WeaponFire(a, Weapon::Awp));
+            state.handle_game_event(&hurt(b, a, 0, "awp", 115, 2));
+            state.handle_game_event(&Event::PlayerDeath(Kill {
+                weapon: Weapon::Awp,
+                ..kill(b, Some(a), None, false)
+            }));
+            state.handle_game_event(&round_end(true));
+            state.handle_game_event(&Event::RoundOfficiallyEnded);
+        }
+        state.handle_game_event(&Event::RoundStart);
+        state.handle_game_event(&Event::WeaponFire(
+            b,
+            Weapon::from_name("weapon_usp_silencer"),
+        ));
+        state.handle_game_event(&hurt(a, b, 0, "usp_silencer", 140, 1));
+        state.handle_game_event(&Event::PlayerDeath(Kill {
+            weapon: Weapon::UspS,
+            headshot: true,
+            ..kill(a, Some(b), None, false)
+        }));
+        state.handle_game_event(&round_end(false));
+        state.handle_game_event(&Event::RoundOfficiallyEnded);
+        state.finish_bursts();
+
+        let player = state.player_by_user_id(a).unwrap();
+        let awp = player.weapons[&Weapon::Awp];
+        assert_eq!((2, 0, 200), (awp.kills, awp.deaths, awp.damage));
+        assert_eq!(100.0, awp.shots.accuracy());
+        assert_eq!(2.0 / 3.0, player.kills_per_round(&Weapon::Awp));
+        assert_eq!(1, player.weapons[&Weapon::UspS].deaths);
+
+        let usp = state.player_by_user_id(b).unwrap().weapons[&Weapon::UspS];
+        assert_eq!((1, 1, 100), (usp.kills, usp.headshot_kills, usp.damage));
+        assert_eq!(WeaponCategory::Sniper, Weapon::Awp.category());
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut state = StatsHandler::new();
+        state.on_header(&Header {
+            playback_time: 1.0,
+            playback_ticks: 1,
+            ..Default::default()
+        });
+        let victim = gen_player(&mut state, 1, false);
+
+        state.current_tick = 10;
+        state.update_player_info(player_info(3, 7, "Player", false));
+        state.handle_game_event(&Event::PlayerSpawn(3, true));
+        state.update_stats(&kill(victim, Some(3), None, false));
+
+        state.current_tick = 20;
+        state.handle_game_event(&Event::PlayerDisconnect(3, "Disconnect".to_string()));
+        assert!(!state.player_by_user_id(3).unwrap().is_connected());
+
+        state.current_tick = 28;
+        state.handle_game_event(&Event::PlayerConnect(9));
+        state.current_tick = 30;
+        state.update_player_info(player_info(9, 7, "Renamed", false));
+        state.update_stats(&kill(victim, Some(9), None, false));
+
+        let player = state.player_by_user_id(9).unwrap();
+        assert_eq!("Renamed", player.info.name);
+        assert_eq!("Renamed", player.name);
+        assert_eq!(2, player.kills);
+        assert!(player.is_connected());
+        assert_eq!(vec![3, 9], player.user_ids().collect::<Vec<_>>());
+        assert_eq!(
+            vec![
+                Connection {
+                    user_id: 3,
+                    connect_tick: 10,
+                    disconnect_tick: Some(20),
+                },
+                Connection {
+                    user_id: 9,
+                    connect_tick: 28,
+                    disconnect_tick: None,
+                },
+            ],
+            player.connections
+        );
+        assert_eq!(2, state.players().count());
+
+        // Renaming while connected updates the same user id
+        state.update_player_info(player_info(9, 7, "Renamed again", false));
+        assert_eq!("Renamed again", state.player_by_user_id(3).unwrap().name);
+    }
+
+    #[test]
+    fn bots_are_merged_by_name() {
+        let mut state = StatsHandler::new();
+        state.update_player_info(player_info(2, 0, "BOT Alex", true));
+        state.update_player_info(player_info(3, 0, "BOT Bert", true));
+        state.handle_disconnect(2, "Kicked");
+        state.update_player_info(player_info(4, 0, "BOT Alex", true));
+
+        assert_eq!(2, state.players().count());
+        let alex = state.player_by_user_id(4).unwrap();
+        assert_eq!(vec![2, 4], alex.user_ids().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn overtime() {
+        let mut state = StatsHandler::new();
+        state.rules.overtime_enabled = true;
+        let a = gen_player(&mut state, 1, true);
+        let b = gen_player(&mut state, 2, false);
+
+        // 15-15 in regulation, then 4-2 in the first overtime
+        let a_wins = (0..30)
+            .map(|i| i % 2 == 0)
+            .chain(vec![true, true, false, true, false, true]);
+        for (i, a_won) in a_wins.enumerate() {
+            let round = i as i32 + 1;
+            state.handle_game_event(&Event::RoundStart);
+            // Sides in the events are as they are in the round
+            let swapped = state.rules.sides_swapped(round);
+            state.handle_game_event(&Event::PlayerSpawn(a, !swapped));
+            state.handle_game_event(&Event::PlayerSpawn(b, swapped));
+            state.handle_game_event(&round_end(a_won != swapped));
+            if round < 36 {
+                state.handle_game_event(&Event::RoundOfficiallyEnded);
+            }
+        }
+
+        assert_eq!((19, 17), state.score);
+        assert!(state.rules.is_match_over(state.score));
+        assert_eq!(
+            vec![
+                (Period::FirstHalf, (8, 7)),
+                (Period::SecondHalf, (7, 8)),
+                (Period::Overtime { number: 1, half: 1 }, (2, 1)),
+                (Period::Overtime { number: 1, half: 2 }, (2, 1)),
+            ],
+            state.periods
+        );
+        assert_eq!(Some(&true), state.teams.get(&a));
+        assert_eq!(Some(&false), state.teams.get(&b));
+
+        let player = state.player_by_user_id(a).unwrap();
+        assert_eq!(36, player.rounds);
+        assert_eq!(100.0, player.kast_percentage());
+    }
 }
@@ -1,5 +1,114 @@
+use std::collections::BTreeMap;
+
+use crate::game_event::HitGroup;
+use crate::grenade::GrenadeType;
 use crate::playerinfo::PlayerInfo;
+use crate::weapon::Weapon;
+
+/// One connection of a player to the server, under one user id.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Connection {
+    pub user_id: i32,
+    pub connect_tick: i32,
+    /// `None` if the player was still connected when the demo ended.
+    pub disconnect_tick: Option<i32>,
+}
+
+/// Shots fired and the hits they made.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct ShotStats {
+    pub shots: i32,
+    /// Shots that hit a player. A shot counts once even if it hit several.
+    pub hits: i32,
+    pub headshot_hits: i32,
+    /// Shots fired on their own, with a pause before the next one.
+    pub taps: i32,
+    pub tap_hits: i32,
+    /// Shots fired in quick succession with others.
+    pub spray_shots: i32,
+    pub spray_hits: i32,
+}
+
+impl ShotStats {
+    /// Share of shots that hit, in percent.
+    pub fn accuracy(&self) -> f32 {
+        percentage(self.hits, self.shots)
+    }
+
+    /// Share of hits that were headshots, in percent.
+    pub fn headshot_percentage(&self) -> f32 {
+        percentage(self.headshot_hits, self.hits)
+    }
+
+    pub fn tap_accuracy(&self) -> f32 {
+        percentage(self.tap_hits, self.taps)
+    }
+
+    pub fn spray_accuracy(&self) -> f32 {
+        percentage(self.spray_hits, self.spray_shots)
+    }
+
+    fn add(&mut self, other: &ShotStats) {
+        self.shots += other.shots;
+        self.hits += other.hits;
+        self.headshot_hits += other.headshot_hits;
+        self.taps += other.taps;
+        self.tap_hits += other.tap_hits;
+        self.spray_shots += other.spray_shots;
+        self.spray_hits += other.spray_hits;
+    }
+}
 
+/// Stats of a player with one weapon.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct WeaponStats {
+    pub kills: i32,
+    pub headshot_kills: i32,
+    /// Deaths to the weapon.
+    pub deaths: i32,
+    /// Damage to enemies, counted like [`Player::damage`].
+    pub damage: i32,
+    pub shots: ShotStats,
+}
+
+/// Players blinded by the flashbangs of a player.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub struct FlashStats {
+    pub enemies_flashed: i32,
+    pub teammates_flashed: i32,
+    pub self_flashes: i32,
+    /// Total time enemies were blind, in milliseconds.
+    pub enemy_blind_ms: i32,
+    /// Enemies killed by the player's team shortly after the player flashed
+    /// them, whether or not the game counted a flash assist.
+    pub flash_kills: i32,
+}
+
+impl FlashStats {
+    /// Total time enemies were blind, in seconds.
+    pub fn enemy_blind_time(&self) -> f32 {
+        self.enemy_blind_ms as f32 / 1000.0
+    }
+
+    /// Average time an enemy was blind, in seconds.
+    pub fn average_blind_time(&self) -> f32 {
+        if self.enemies_flashed == 0 {
+            0.0
+        } else {
+            self.enemy_blind_time() / self.enemies_flashed as f32
+        }
+    }
+}
+
+fn percentage(part: i32, total: i32) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * part as f32 / total as f32
+    }
+}
+
+/// A player and their stats, merged across reconnects.
 #[derive(Clone, Debug, Hash)]
 pub struct Player {
     pub name: String,
@@ -9,7 +118,38 @@ pub struct Player {
     pub flash_assists: i32,
     pub deaths: i32,
 
+    pub headshot_kills: i32,
+    /// Kills through walls or other objects.
+    pub wallbang_kills: i32,
+    pub noscope_kills: i32,
+    pub smoke_kills: i32,
+    /// Kills while the player was blind.
+    pub blind_kills: i32,
+
     pub kast: i32,
+    /// Rounds the player was in, the denominator of KAST.
+    pub rounds: i32,
+
+    /// Health damage dealt to enemies, not counting damage beyond the
+    /// health the victim had left.
+    pub damage: i32,
+    pub damage_by_hitgroup: BTreeMap<HitGroup, i32>,
+    /// Part of `damage` dealt with HE grenades and fire.
+    pub utility_damage: i32,
+
+    pub mvps: i32,
+
+    pub plants: i32,
+    pub defuses: i32,
+    /// Defuses started but not finished.
+    pub aborted_defuses: i32,
+
+    pub grenades_thrown: BTreeMap<GrenadeType, i32>,
+    pub flashes: FlashStats,
+
+    pub weapons: BTreeMap<Weapon, WeaponStats>,
+    /// Shots by round, counting from 1.
+    pub shots_by_round: BTreeMap<i32, ShotStats>,
 
     pub equipped: String,
 
@@ -17,6 +157,7 @@ pub struct Player {
     pub muna_tick: i32,
 
     pub info: PlayerInfo,
+    pub connections: Vec<Connection>,
 }
 
 impl Player {
@@ -28,13 +169,101 @@ impl Player {
             flash_assists: 0,
             deaths: 0,
 
+            headshot_kills: 0,
+            wallbang_kills: 0,
+            noscope_kills: 0,
+            smoke_kills: 0,
+            blind_kills: 0,
+
             kast: 0,
+            rounds: 0,
+
+            damage: 0,
+            damage_by_hitgroup: BTreeMap::new(),
+            utility_damage: 0,
+
+            mvps: 0,
+
+            plants: 0,
+            defuses: 0,
+            aborted_defuses: 0,
+
+            grenades_thrown: BTreeMap::new(),
+            flashes: FlashStats::default(),
+
+            weapons: BTreeMap::new(),
+            shots_by_round: BTreeMap::new(),
 
             equipped: "knife".to_string(),
             latest_muna: None,
             muna_tick: 0,
 
             info,
+            connections: Vec::new(),
+        }
+    }
+
+    /// Replace the userinfo, eg. after a rename or reconnect.
+    pub fn set_info(&mut self, info: PlayerInfo) {
+        self.name = info.name.clone();
+        self.info = info;
+    }
+
+    /// Share of rounds with a kill, assist, survival or trade, in percent.
+    pub fn kast_percentage(&self) -> f32 {
+        percentage(self.kast, self.rounds)
+    }
+
+    /// Share of kills that were headshots, in percent.
+    pub fn headshot_percentage(&self) -> f32 {
+        percentage(self.headshot_kills, self.kills)
+    }
+
+    /// Average damage per round.
+    pub fn adr(&self) -> f32 {
+        if self.rounds == 0 {
+            0.0
+        } else {
+            self.damage as f32 / self.rounds as f32
+        }
+    }
+
+    /// Shots with all weapons.
+    pub fn shots(&self) -> ShotStats {
+        let mut total = ShotStats::default();
+        for stats in self.weapons.values() {
+            total.add(&stats.shots);
+        }
+        total
+    }
+
+    /// Average kills per round with a weapon.
+    pub fn kills_per_round(&self, weapon: &Weapon) -> f32 {
+        let kills = self.weapons.get(weapon).map_or(0, |stats| stats.kills);
+        if self.rounds == 0 {
+            0.0
+        } else {
+            kills as f32 / self.rounds as f32
         }
     }
+
+    /// Every user id the player had, in connection order.
+    pub fn user_ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.connections.iter().map(|c| c.user_id)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connections
+            .last()
+            .is_some_and(|c| c.disconnect_tick.is_none())
+    }
+
+    /// Reset the stats, keeping the identity and connections.
+    pub fn reset_stats(&mut self) {
+        let connections = std::mem::take(&mut self.connections);
+        *self = Player {
+            connections,
+            ..Player::new(self.info.clone())
+        };
+    }
 }
@@ -32,12 +32,12 @@ impl PlayerInfo {
         let fake = buf.read_u8()?;
         let proxy = buf.read_u8()?;
         let custom_files_crc = [
-            buf.read_u32_be().unwrap(),
-            buf.read_u32_be().unwrap(),
-            buf.read_u32_be().unwrap(),
-            buf.read_u32_be().unwrap(),
+            buf.read_u32_be()?,
+            buf.read_u32_be()?,
+            buf.read_u32_be()?,
+            buf.read_u32_be()?,
         ];
-        let files_downloaded = buf.read_u8().unwrap();
+        let files_downloaded = buf.read_u8()?;
 
         let entity_id = entry_index;
 
@@ -0,0 +1,580 @@
+use std::io::Read;
+
+use crate::bitreader::{BitReader, ReadExtras};
+use crate::datatables::*;
+use crate::error::{DemoError, Result};
+
+const COORD_INTEGER_BITS: u8 = 14;
+const COORD_INTEGER_BITS_MP: u8 = 11;
+const COORD_FRACTIONAL_BITS: u8 = 5;
+const COORD_FRACTIONAL_BITS_MP_LOWPRECISION: u8 = 3;
+const NORMAL_FRACTIONAL_BITS: u8 = 11;
+const DT_MAX_STRING_BITS: u8 = 9;
+
+/// Decoded value of a networked entity property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropValue {
+    Int(i32),
+    Float(f32),
+    Vector([f32; 3]),
+    VectorXY([f32; 2]),
+    String(String),
+    Array(Vec<PropValue>),
+    Int64(i64),
+}
+
+impl PropValue {
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            PropValue::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            PropValue::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector(&self) -> Option<[f32; 3]> {
+        match self {
+            PropValue::Vector(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+fn flags(prop: &SendProp) -> i32 {
+    prop.flags.unwrap_or(0)
+}
+
+fn read_signed_bits<R: Read>(reader: &mut BitReader<R>, bits: u8) -> Result<i32> {
+    if bits == 0 {
+        return Ok(0);
+    }
+    let value = reader.read_bits_u32(bits)?;
+    let shift = 32 - bits as u32;
+    Ok(((value << shift) as i32) >> shift)
+}
+
+fn read_var_u64<R: Read>(reader: &mut BitReader<R>, max_bytes: u8) -> Result<u64> {
+    let mut result = 0;
+    for byte in 0..max_bytes {
+        let num = reader.read_bits_u32(8)? as u64;
+        result |= (num & 0x7F) << (byte * 7);
+        if num & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+fn read_bit_coord<R: Read>(reader: &mut BitReader<R>) -> Result<f32> {
+    let has_int = reader.read_bit()?;
+    let has_fract = reader.read_bit()?;
+    if !has_int && !has_fract {
+        return Ok(0.0);
+    }
+    let negative = reader.read_bit()?;
+    let int = if has_int {
+        reader.read_bits_u32(COORD_INTEGER_BITS)? + 1
+    } else {
+        0
+    };
+    let fract = if has_fract {
+        reader.read_bits_u32(COORD_FRACTIONAL_BITS)?
+    } else {
+        0
+    };
+    let value = int as f32 + fract as f32 / (1 << COORD_FRACTIONAL_BITS) as f32;
+    Ok(if negative { -value } else { value })
+}
+
+fn read_bit_coord_mp<R: Read>(
+    reader: &mut BitReader<R>,
+    integral: bool,
+    low_precision: bool,
+) -> Result<f32> {
+    let in_bounds = reader.read_bit()?;
+    let int_bits = if in_bounds {
+        COORD_INTEGER_BITS_MP
+    } else {
+        COORD_INTEGER_BITS
+    };
+    if integral {
+        if !reader.read_bit()? {
+            return Ok(0.0);
+        }
+        let negative = reader.read_bit()?;
+        let value = (reader.read_bits_u32(int_bits)? + 1) as f32;
+        return Ok(if negative { -value } else { value });
+    }
+
+    let has_int = reader.read_bit()?;
+    let negative = reader.read_bit()?;
+    let int = if has_int {
+        reader.read_bits_u32(int_bits)? + 1
+    } else {
+        0
+    };
+    let fract_bits = if low_precision {
+        COORD_FRACTIONAL_BITS_MP_LOWPRECISION
+    } else {
+        COORD_FRACTIONAL_BITS
+    };
+    let fract = reader.read_bits_u32(fract_bits)?;
+    let value = int as f32 + fract as f32 / (1 << fract_bits) as f32;
+    Ok(if negative { -value } else { value })
+}
+
+fn read_bit_cell_coord<R: Read>(
+    reader: &mut BitReader<R>,
+    bits: u8,
+    integral: bool,
+    low_precision: bool,
+) -> Result<f32> {
+    let int = reader.read_bits_u32(bits)?;
+    if integral {
+        return Ok(int as f32);
+    }
+    let fract_bits = if low_precision {
+        COORD_FRACTIONAL_BITS_MP_LOWPRECISION
+    } else {
+        COORD_FRACTIONAL_BITS
+    };
+    let fract = reader.read_bits_u32(fract_bits)?;
+    Ok(int as f32 + fract as f32 / (1 << fract_bits) as f32)
+}
+
+fn read_bit_normal<R: Read>(reader: &mut BitReader<R>) -> Result<f32> {
+    let negative = reader.read_bit()?;
+    let fract = reader.read_bits_u32(NORMAL_FRACTIONAL_BITS)?;
+    let value = fract as f32 / ((1 << NORMAL_FRACTIONAL_BITS) - 1) as f32;
+    Ok(if negative { -value } else { value })
+}
+
+fn num_bits(prop: &SendProp) -> u8 {
+    prop.num_bits.unwrap_or(0) as u8
+}
+
+/// Decode a `DPT_Int` prop: varint, unsigned or sign-extended bits.
+pub fn decode_int<R: Read>(reader: &mut BitReader<R>, prop: &SendProp) -> Result<i32> {
+    let flags = flags(prop);
+    if flags & SPROP_VARINT != 0 {
+        let value = read_var_u64(reader, 5)? as u32;
+        if flags & SPROP_UNSIGNED != 0 {
+            Ok(value as i32)
+        } else {
+            Ok(((value >> 1) as i32) ^ -((value & 1) as i32))
+        }
+    } else if flags & SPROP_UNSIGNED != 0 {
+        Ok(reader.read_bits_u32(num_bits(prop))? as i32)
+    } else {
+        read_signed_bits(reader, num_bits(prop))
+    }
+}
+
+/// Decode a `DPT_Float` prop according to its encoding flags.
+pub fn decode_float<R: Read>(reader: &mut BitReader<R>, prop: &SendProp) -> Result<f32> {
+    let flags = flags(prop);
+    let bits = num_bits(prop);
+    if flags & SPROP_COORD != 0 {
+        read_bit_coord(reader)
+    } else if flags & SPROP_COORD_MP != 0 {
+        read_bit_coord_mp(reader, false, false)
+    } else if flags & SPROP_COORD_MP_LOWPRECISION != 0 {
+        read_bit_coord_mp(reader, false, true)
+    } else if flags & SPROP_COORD_MP_INTEGRAL != 0 {
+        read_bit_coord_mp(reader, true, false)
+    } else if flags & SPROP_NOSCALE != 0 {
+        Ok(f32::from_bits(reader.read_bits_u32(32)?))
+    } else if flags & SPROP_NORMAL != 0 {
+        read_bit_normal(reader)
+    } else if flags & SPROP_CELL_COORD != 0 {
+        read_bit_cell_coord(reader, bits, false, false)
+    } else if flags & SPROP_CELL_COORD_LOWPRECISION != 0 {
+        read_bit_cell_coord(reader, bits, false, true)
+    } else if flags & SPROP_CELL_COORD_INTEGRAL != 0 {
+        read_bit_cell_coord(reader, bits, true, false)
+    } else {
+        let interp = reader.read_bits_u32(bits)?;
+        let low = prop.low_value.unwrap_or(0.0);
+        let high = prop.high_value.unwrap_or(0.0);
+        let fraction = interp as f32 / ((1u64 << bits) - 1) as f32;
+        Ok(low + (high - low) * fraction)
+    }
+}
+
+/// Decode a `DPT_Int64` prop.
+pub fn decode_int64<R: Read>(reader: &mut BitReader<R>, prop: &SendProp) -> Result<i64> {
+    let flags = flags(prop);
+    let bits = num_bits(prop);
+    if flags & SPROP_VARINT != 0 {
+        let value = read_var_u64(reader, 10)?;
+        if flags & SPROP_UNSIGNED != 0 {
+            Ok(value as i64)
+        } else {
+            Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+        }
+    } else if flags & SPROP_UNSIGNED != 0 {
+        let low = reader.read_bits_u32(32)? as u64;
+        let high = reader.read_bits_u32(bits.saturating_sub(32))? as u64;
+        Ok(((high << 32) | low) as i64)
+    } else {
+        let negative = reader.read_bit()?;
+        let low = reader.read_bits_u32(32)? as u64;
+        let high = reader.read_bits_u32(bits.saturating_sub(33))? as u64;
+        let value = ((high << 32) | low) as i64;
+        Ok(if negative { -value } else { value })
+    }
+}
+
+/// Decode a `DPT_Vector` prop. Normals only network the sign of z.
+pub fn decode_vector<R: Read>(reader: &mut BitReader<R>, prop: &SendProp) -> Result<[f32; 3]> {
+    let x = decode_float(reader, prop)?;
+    let y = decode_float(reader, prop)?;
+    let z = if flags(prop) & SPROP_NORMAL != 0 {
+        let negative = reader.read_bit()?;
+        let sum = x * x + y * y;
+        let z = if sum < 1.0 { (1.0 - sum).sqrt() } else { 0.0 };
+        if negative {
+            -z
+        } else {
+            z
+        }
+    } else {
+        decode_float(reader, prop)?
+    };
+    Ok([x, y, z])
+}
+
+/// Decode a `DPT_VectorXY` prop.
+pub fn decode_vector_xy<R: Read>(reader: &mut BitReader<R>, prop: &SendProp) -> Result<[f32; 2]> {
+    let x = decode_float(reader, prop)?;
+    let y = decode_float(reader, prop)?;
+    Ok([x, y])
+}
+
+/// Decode a `DPT_String` prop.
+pub fn decode_string<R: Read>(reader: &mut BitReader<R>) -> Result<String> {
+    let len = reader.read_bits_u32(DT_MAX_STRING_BITS)?;
+    let bytes = reader.read_u8_vec(len as usize)?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+/// Decode a `DPT_Array` prop, whose elements are described by `element`.
+pub fn decode_array<R: Read>(
+    reader: &mut BitReader<R>,
+    prop: &SendProp,
+    element: &SendProp,
+) -> Result<Vec<PropValue>> {
+    let max_elements = prop.num_elements.unwrap_or(0).max(1) as u32;
+    let bits = (32 - max_elements.leading_zeros()) as u8;
+    let count = reader.read_bits_u32(bits)?;
+    (0..count)
+        .map(|_| decode_prop(reader, element, None))
+        .collect()
+}
+
+/// Decode any prop. `array_element` is required for `DPT_Array` props.
+pub fn decode_prop<R: Read>(
+    reader: &mut BitReader<R>,
+    prop: &SendProp,
+    array_element: Option<&SendProp>,
+) -> Result<PropValue> {
+    Ok(match prop.r#type.unwrap_or(-1) {
+        DPT_INT => PropValue::Int(decode_int(reader, prop)?),
+        DPT_FLOAT => PropValue::Float(decode_float(reader, prop)?),
+        DPT_VECTOR => PropValue::Vector(decode_vector(reader, prop)?),
+        DPT_VECTOR_XY => PropValue::VectorXY(decode_vector_xy(reader, prop)?),
+        DPT_STRING => PropValue::String(decode_string(reader)?),
+        DPT_ARRAY => {
+            let element = array_element.ok_or_else(|| {
+                DemoError::EntityCorruption("array prop without element".to_string())
+            })?;
+            PropValue::Array(decode_array(reader, prop, element)?)
+        }
+        DPT_INT64 => PropValue::Int64(decode_int64(reader, prop)?),
+        other => {
+            return Err(DemoError::EntityCorruption(format!(
+                "unknown prop type {}",
+                other
+            )))
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitreader::BitWriter;
+    use crate::datatables::test::prop;
+    use crate::propdecode::*;
+
+    const MARKER: u32 = 0b101;
+
+    /// Decode `w` followed by a marker, checking that exactly the prop's
+    /// bits were consumed.
+    fn decode(prop: &SendProp, element: Option<&SendProp>, w: &mut BitWriter) -> PropValue {
+        let data = w.write_bits(MARKER, 3).finish();
+        let mut reader = BitReader::new(&data[..]);
+        let value = decode_prop(&mut reader, prop, element).unwrap();
+        assert_eq!(MARKER, reader.read_bits_u32(3).unwrap());
+        value
+    }
+
+    fn float(flags: i32, num_bits: i32) -> SendProp {
+        prop("f", DPT_FLOAT, flags, num_bits)
+    }
+
+    #[test]
+    fn int() {
+        let unsigned = prop("i", DPT_INT, SPROP_UNSIGNED, 7);
+        let v = decode(&unsigned, None, BitWriter::new().write_bits(100, 7));
+        assert_eq!(PropValue::Int(100), v);
+
+        let signed = prop("i", DPT_INT, 0, 6);
+        let v = decode(&signed, None, BitWriter::new().write_bits(0b111101, 6));
+        assert_eq!(PropValue::Int(-3), v);
+
+        let full = prop("i", DPT_INT, 0, 32);
+        let v = decode(&full, None, BitWriter::new().write_bits(u32::MAX, 32));
+        assert_eq!(PropValue::Int(-1), v);
+    }
+
+    #[test]
+    fn varint() {
+        let unsigned = prop("i", DPT_INT, SPROP_VARINT | SPROP_UNSIGNED, 32);
+        let v = decode(&unsigned, None, BitWriter::new().write_bytes(&[0xAC, 0x02]));
+        assert_eq!(PropValue::Int(300), v);
+
+        let signed = prop("i", DPT_INT, SPROP_VARINT, 32);
+        let v = decode(&signed, None, BitWriter::new().write_bytes(&[0x05]));
+        assert_eq!(PropValue::Int(-3), v);
+        let v = decode(&signed, None, BitWriter::new().write_bytes(&[0x06]));
+        assert_eq!(PropValue::Int(3), v);
+    }
+
+    #[test]
+    fn quantized_float() {
+        let prop = SendProp {
+            low_value: Some(0.0),
+            high_value: Some(10.0),
+            ..float(0, 4)
+        };
+        let v = decode(&prop, None, BitWriter::new().write_bits(3, 4));
+        assert_eq!(PropValue::Float(2.0), v);
+        let v = decode(&prop, None, BitWriter::new().write_bits(15, 4));
+        assert_eq!(PropValue::Float(10.0), v);
+    }
+
+    #[test]
+    fn coord() {
+        let prop = float(SPROP_COORD, 0);
+        let v = decode(&prop, None, BitWriter::new().write_bits(0, 2));
+        assert_eq!(PropValue::Float(0.0), v);
+
+        let mut w = BitWriter::new();
+        w.write_bit(true).write_bit(true).write_bit(true);
+        w.write_bits(9, 14).write_bits(16, 5);
+        assert_eq!(PropValue::Float(-10.5), decode(&prop, None, &mut w));
+    }
+
+    #[test]
+    fn coord_mp() {
+        let prop = float(SPROP_COORD_MP, 0);
+        let mut w = BitWriter::new();
+        w.write_bit(true).write_bit(true).write_bit(false);
+        w.write_bits(99, 11).write_bits(8, 5);
+        assert_eq!(PropValue::Float(100.25), decode(&prop, None, &mut w));
+
+        let prop = float(SPROP_COORD_MP_LOWPRECISION, 0);
+        let mut w = BitWriter::new();
+        w.write_bit(false).write_bit(true).write_bit(true);
+        w.write_bits(1999, 14).write_bits(4, 3);
+        assert_eq!(PropValue::Float(-2000.5), decode(&prop, None, &mut w));
+
+        let prop = float(SPROP_COORD_MP_INTEGRAL, 0);
+        let mut w = BitWriter::new();
+        w.write_bit(true)
+            .write_bit(true)
+            .write_bit(true)
+            .write_bits(41, 11);
+        assert_eq!(PropValue::Float(-42.0), decode(&prop, None, &mut w));
+        let v = decode(&prop, None, BitWriter::new().write_bits(0, 2));
+        assert_eq!(PropValue::Float(0.0), v);
+    }
+
+    #[test]
+    fn no_scale() {
+        let prop = float(SPROP_NOSCALE, 32);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bits(1.5f32.to_bits(), 32),
+        );
+        assert_eq!(PropValue::Float(1.5), v);
+    }
+
+    #[test]
+    fn normal() {
+        let prop = float(SPROP_NORMAL, 0);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bit(true).write_bits(2047, 11),
+        );
+        assert_eq!(PropValue::Float(-1.0), v);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bit(false).write_bits(0, 11),
+        );
+        assert_eq!(PropValue::Float(0.0), v);
+    }
+
+    #[test]
+    fn cell_coord() {
+        let prop = float(SPROP_CELL_COORD, 10);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bits(513, 10).write_bits(24, 5),
+        );
+        assert_eq!(PropValue::Float(513.75), v);
+
+        let prop = float(SPROP_CELL_COORD_LOWPRECISION, 10);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bits(513, 10).write_bits(2, 3),
+        );
+        assert_eq!(PropValue::Float(513.25), v);
+
+        let prop = float(SPROP_CELL_COORD_INTEGRAL, 10);
+        let v = decode(&prop, None, BitWriter::new().write_bits(7, 10));
+        assert_eq!(PropValue::Float(7.0), v);
+    }
+
+    #[test]
+    fn vector() {
+        let prop = prop("v", DPT_VECTOR, SPROP_NOSCALE, 32);
+        let mut w = BitWriter::new();
+        for n in &[1.0f32, 2.0, -3.0] {
+            w.write_bits(n.to_bits(), 32);
+        }
+        assert_eq!(
+            PropValue::Vector([1.0, 2.0, -3.0]),
+            decode(&prop, None, &mut w)
+        );
+    }
+
+    #[test]
+    fn normal_vector() {
+        // Only x and y are networked, z is derived from them and a sign bit.
+        let prop = prop("v", DPT_VECTOR, SPROP_NORMAL, 0);
+        let mut w = BitWriter::new();
+        w.write_bit(false).write_bits(0, 11);
+        w.write_bit(false).write_bits(0, 11);
+        w.write_bit(true);
+        assert_eq!(
+            PropValue::Vector([0.0, 0.0, -1.0]),
+            decode(&prop, None, &mut w)
+        );
+    }
+
+    #[test]
+    fn vector_xy() {
+        let prop = prop("v", DPT_VECTOR_XY, SPROP_NOSCALE, 32);
+        let mut w = BitWriter::new();
+        w.write_bits((-4.5f32).to_bits(), 32);
+        w.write_bits(8.0f32.to_bits(), 32);
+        assert_eq!(
+            PropValue::VectorXY([-4.5, 8.0]),
+            decode(&prop, None, &mut w)
+        );
+    }
+
+    #[test]
+    fn string() {
+        let prop = prop("s", DPT_STRING, 0, 0);
+        let v = decode(
+            &prop,
+            None,
+            BitWriter::new().write_bits(5, 9).write_bytes(b"hello"),
+        );
+        assert_eq!(PropValue::String("hello".to_string()), v);
+    }
+
+    #[test]
+    fn array() {
+        let element = prop("a", DPT_INT, SPROP_UNSIGNED, 4);
+        let array = SendProp {
+            num_elements: Some(10),
+            ..prop("a", DPT_ARRAY, 0, 0)
+        };
+        // 10 elements need a 4 bit count
+        let mut w = BitWriter::new();
+        w.write_bits(3, 4)
+            .write_bits(1, 4)
+            .write_bits(2, 4)
+            .write_bits(3, 4);
+        assert_eq!(
+            PropValue::Array(vec![
+                PropValue::Int(1),
+                PropValue::Int(2),
+                PropValue::Int(3)
+            ]),
+            decode(&array, Some(&element), &mut w)
+        );
+
+        let mut reader = BitReader::new(&[0u8][..]);
+        assert!(matches!(
+            decode_prop(&mut reader, &array, None),
+            Err(DemoError::EntityCorruption(_))
+        ));
+    }
+
+    #[test]
+    fn int64() {
+        let unsigned = prop("l", DPT_INT64, SPROP_UNSIGNED, 64);
+        let v = decode(
+            &unsigned,
+            None,
+            BitWriter::new().write_bits(2, 32).write_bits(1, 32),
+        );
+        assert_eq!(PropValue::Int64(0x1_0000_0002), v);
+
+        let signed = prop("l", DPT_INT64, 0, 64);
+        let mut w = BitWriter::new();
+        w.write_bit(true).write_bits(5, 32).write_bits(0, 31);
+        assert_eq!(PropValue::Int64(-5), decode(&signed, None, &mut w));
+
+        let varint = prop("l", DPT_INT64, SPROP_VARINT | SPROP_UNSIGNED, 64);
+        let mut bytes = Vec::new();
+        prost::encoding::encode_varint(1 << 40, &mut bytes);
+        let v = decode(&varint, None, BitWriter::new().write_bytes(&bytes));
+        assert_eq!(PropValue::Int64(1 << 40), v);
+
+        let zigzag = prop("l", DPT_INT64, SPROP_VARINT, 64);
+        let v = decode(&zigzag, None, BitWriter::new().write_bytes(&[0x03]));
+        assert_eq!(PropValue::Int64(-2), v);
+    }
+
+    #[test]
+    fn unknown_type() {
+        let mut reader = BitReader::new(&[0u8][..]);
+        assert!(matches!(
+            decode_prop(&mut reader, &prop("x", DPT_DATA_TABLE, 0, 0), None),
+            Err(DemoError::EntityCorruption(_))
+        ));
+    }
+}
@@ -2,8 +2,8 @@ use rusqlite::{params, Connection, Transaction};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 
-use crate::header::Header;
-use crate::player::Player;
+use demoinfo::{Header, Player};
+
 use crate::stable_hasher::StableHasher;
 
 use log::warn;
@@ -15,6 +15,7 @@ pub struct RankManager {
 type Rank = f64;
 const DEFAULT_RANK: Rank = 1000.0;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct DbPlayer {
     xuid: i64,
@@ -52,7 +53,7 @@ pub fn team_rank(
 ) -> rusqlite::Result<(Rank, HashMap<i64, DbPlayer>)> {
     let ranks = team
         .iter()
-        .map(|p| Ok((p.info.xuid, fetch_player(tx, p.info.xuid as i64)?)))
+        .map(|p| Ok((p.info.xuid, fetch_player(tx, p.info.xuid)?)))
         .collect::<rusqlite::Result<HashMap<i64, DbPlayer>>>()?;
 
     let rank_sum: Rank = ranks.values().map(|p| p.rank).sum();
@@ -156,7 +157,7 @@ impl RankManager {
             tx.execute(
                 "INSERT OR REPLACE INTO player (xuid, name, rank, game_count) VALUES (?, ?, ?, ?)",
                 params![
-                    &(player.info.xuid as i64),
+                    &player.info.xuid,
                     &player.name,
                     dbplayer.rank + points * share_of_points,
                     dbplayer.game_count + 1
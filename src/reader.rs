@@ -0,0 +1,197 @@
+use std::io::Read;
+
+use crate::bitreader::{CountingReader, ReadExtras};
+use crate::error::Result;
+use crate::header::Header;
+use crate::packet::{
+    read_raw_data, CmdType, ConsoleCmd, CustomData, DemoCmdInfo, PacketHeader, UserCmd,
+};
+
+/// Contents of a frame, depending on its command type.
+#[derive(Clone, Debug)]
+pub enum FramePayload {
+    /// `dem_synctick` and `dem_stop` carry no data.
+    None,
+    /// `dem_signon` and `dem_packet`: net messages for one tick.
+    Packet {
+        splits: [DemoCmdInfo; 2],
+        sequence_in: u32,
+        sequence_out: u32,
+        data: Vec<u8>,
+    },
+    ConsoleCmd(ConsoleCmd),
+    UserCmd(UserCmd),
+    /// Raw `dem_datatables` payload, see [`DataTables::new`](crate::DataTables::new).
+    DataTables(Vec<u8>),
+    CustomData(CustomData),
+    /// Raw `dem_stringtables` payload.
+    StringTables(Vec<u8>),
+}
+
+/// One frame of a demo.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub header: PacketHeader,
+    /// Byte offset of the frame header in the demo.
+    pub offset: u64,
+    pub payload: FramePayload,
+}
+
+impl Frame {
+    pub fn tick(&self) -> i32 {
+        self.header.tick
+    }
+}
+
+/// Iterator over the frames of a demo, without interpreting them.
+///
+/// Iteration stops after the `dem_stop` frame or the first error.
+pub struct DemoReader<R: Read> {
+    reader: CountingReader<R>,
+    header: Header,
+    tick: i32,
+    done: bool,
+}
+
+impl<R: Read> DemoReader<R> {
+    /// Read the demo header and prepare to read frames.
+    pub fn new(reader: R) -> Result<DemoReader<R>> {
+        let mut reader = CountingReader::new(reader);
+        let header = Header::new(&mut reader).map_err(|e| e.at(0, 0))?;
+        Ok(DemoReader {
+            reader,
+            header,
+            tick: 0,
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn read_payload(&mut self, cmd_type: CmdType) -> Result<FramePayload> {
+        let r = &mut self.reader;
+        Ok(match cmd_type {
+            CmdType::SyncTick | CmdType::Stop => FramePayload::None,
+            CmdType::SignOn | CmdType::Packet => {
+                let splits = [DemoCmdInfo::new(r)?, DemoCmdInfo::new(r)?];
+                let sequence_in = r.read_u32()?;
+                let sequence_out = r.read_u32()?;
+                let size = r.read_u32()?;
+                FramePayload::Packet {
+                    splits,
+                    sequence_in,
+                    sequence_out,
+                    data: r.read_u8_vec(size as usize)?,
+                }
+            }
+            CmdType::ConsoleCmd => FramePayload::ConsoleCmd(ConsoleCmd::new(r)?),
+            CmdType::UserCmd => FramePayload::UserCmd(UserCmd::new(r)?),
+            CmdType::DataTables => FramePayload::DataTables(read_raw_data(r)?),
+            CmdType::CustomData => FramePayload::CustomData(CustomData::new(r)?),
+            CmdType::StringTables => FramePayload::StringTables(read_raw_data(r)?),
+        })
+    }
+
+    fn read_frame(&mut self) -> Result<Frame> {
+        let offset = self.reader.position();
+        let header = PacketHeader::new(&mut self.reader).map_err(|e| e.at(offset, self.tick))?;
+        self.tick = header.tick;
+        let payload = self
+            .read_payload(header.cmd_type)
+            .map_err(|e| e.at(offset, header.tick))?;
+        Ok(Frame {
+            header,
+            offset,
+            payload,
+        })
+    }
+}
+
+impl<R: Read> Iterator for DemoReader<R> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Result<Frame>> {
+        if self.done {
+            return None;
+        }
+        let frame = self.read_frame();
+        match &frame {
+            Ok(frame) if frame.header.cmd_type == CmdType::Stop => self.done = true,
+            Ok(_) => {}
+            Err(_) => self.done = true,
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::DemoError;
+    use crate::reader::*;
+
+    fn frame(data: &mut Vec<u8>, cmd_type: u8, tick: i32) {
+        data.push(cmd_type);
+        data.extend_from_slice(&tick.to_le_bytes());
+        data.push(0);
+    }
+
+    #[test]
+    fn frames() {
+        let mut data = include_bytes!("example_header").to_vec();
+        let header_len = data.len() as u64;
+        frame(&mut data, 3, 0);
+        frame(&mut data, 4, 1);
+        data.extend_from_slice(&[5, 0, 0, 0]);
+        data.extend_from_slice(b"say\0\0");
+        frame(&mut data, 2, 2);
+        data.extend_from_slice(&[0; 2 * 76]);
+        data.extend_from_slice(&[1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 7, 8, 9]);
+        frame(&mut data, 7, 2);
+        // Anything after dem_stop is not read
+        data.push(42);
+
+        let reader = DemoReader::new(data.as_slice()).unwrap();
+        assert_eq!("de_vertigo", reader.header().map);
+        let frames = reader.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(4, frames.len());
+
+        assert!(matches!(frames[0].payload, FramePayload::None));
+        assert_eq!(header_len, frames[0].offset);
+        assert_eq!(header_len + 6, frames[1].offset);
+        assert!(
+            matches!(&frames[1].payload, FramePayload::ConsoleCmd(cmd) if cmd.command == "say")
+        );
+
+        assert_eq!(2, frames[2].tick());
+        match &frames[2].payload {
+            FramePayload::Packet {
+                splits,
+                sequence_in,
+                sequence_out,
+                data,
+            } => {
+                assert_eq!(0, splits[1].flags);
+                assert_eq!(1, *sequence_in);
+                assert_eq!(2, *sequence_out);
+                assert_eq!(&vec![7, 8, 9], data);
+            }
+            other => panic!("unexpected payload {:?}", other),
+        }
+        assert_eq!(CmdType::Stop, frames[3].header.cmd_type);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let mut data = include_bytes!("example_header").to_vec();
+        frame(&mut data, 6, 3);
+        data.extend_from_slice(&[100, 0, 0, 0, 1]);
+
+        let mut reader = DemoReader::new(data.as_slice()).unwrap();
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err.kind(), DemoError::TruncatedFrame));
+        assert_eq!(Some(3), err.tick());
+        assert!(reader.next().is_none());
+    }
+}
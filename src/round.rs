@@ -0,0 +1,123 @@
+/// Side of the map a team plays on.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Side {
+    T,
+    CT,
+}
+
+impl Side {
+    /// Side of a team number, as used by `m_iTeamNum` and events.
+    pub fn from_team(team: i32) -> Option<Side> {
+        match team {
+            2 => Some(Side::T),
+            3 => Some(Side::CT),
+            _ => None,
+        }
+    }
+
+    pub fn team_number(self) -> i32 {
+        match self {
+            Side::T => 2,
+            Side::CT => 3,
+        }
+    }
+}
+
+/// Why a round ended, from the `reason` key of `round_end`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum RoundEndReason {
+    BombExploded,
+    BombDefused,
+    /// The terrorists were eliminated.
+    CtWin,
+    /// The counter-terrorists were eliminated.
+    TerroristsWin,
+    Draw,
+    HostagesRescued,
+    /// Time ran out without a bomb plant.
+    TargetSaved,
+    HostagesNotRescued,
+    GameStart,
+    TerroristsSurrender,
+    CtSurrender,
+    Other(i32),
+}
+
+impl From<i32> for RoundEndReason {
+    fn from(reason: i32) -> RoundEndReason {
+        match reason {
+            1 => RoundEndReason::BombExploded,
+            7 => RoundEndReason::BombDefused,
+            8 => RoundEndReason::CtWin,
+            9 => RoundEndReason::TerroristsWin,
+            10 => RoundEndReason::Draw,
+            11 => RoundEndReason::HostagesRescued,
+            12 => RoundEndReason::TargetSaved,
+            13 => RoundEndReason::HostagesNotRescued,
+            16 => RoundEndReason::GameStart,
+            17 => RoundEndReason::TerroristsSurrender,
+            18 => RoundEndReason::CtSurrender,
+            other => RoundEndReason::Other(other),
+        }
+    }
+}
+
+/// Why a player was the MVP of a round, from the `reason` key of `round_mvp`.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum MvpReason {
+    MostEliminations,
+    BombPlanted,
+    BombDefused,
+    HostagesRescued,
+    Other(i32),
+}
+
+impl From<i32> for MvpReason {
+    fn from(reason: i32) -> MvpReason {
+        match reason {
+            1 => MvpReason::MostEliminations,
+            2 => MvpReason::BombPlanted,
+            3 => MvpReason::BombDefused,
+            4 => MvpReason::HostagesRescued,
+            other => MvpReason::Other(other),
+        }
+    }
+}
+
+/// One played round.
+///
+/// Players are identified by the first user id they had, see
+/// [`Player::user_ids`](crate::Player::user_ids).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Round {
+    /// Round number, counting from 1.
+    pub number: i32,
+    pub winner: Side,
+    /// Clan names of the teams on each side, empty if the server had none.
+    pub t_name: String,
+    pub ct_name: String,
+    pub reason: RoundEndReason,
+    /// Message shown at the end of the round, eg. `#SFUI_Notice_Bomb_Defused`.
+    pub message: String,
+    pub mvp: Option<i32>,
+    pub mvp_reason: Option<MvpReason>,
+    /// Seconds from the start of the round, including freeze time, to its end.
+    pub duration: f32,
+    pub t_survivors: i32,
+    pub ct_survivors: i32,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::round::*;
+
+    #[test]
+    fn reasons() {
+        assert_eq!(RoundEndReason::BombDefused, RoundEndReason::from(7));
+        assert_eq!(RoundEndReason::TargetSaved, RoundEndReason::from(12));
+        assert_eq!(RoundEndReason::Other(2), RoundEndReason::from(2));
+        assert_eq!(MvpReason::BombPlanted, MvpReason::from(2));
+        assert_eq!(Some(Side::CT), Side::from_team(3));
+        assert_eq!(None, Side::from_team(1));
+    }
+}
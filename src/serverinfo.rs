@@ -0,0 +1,79 @@
+use crate::csgo::netmessages_public::CsvcMsgServerInfo;
+
+/// Server settings from `svc_ServerInfo`, sent once when the demo starts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerInfo {
+    /// Seconds per tick, `None` if the server did not send a usable one.
+    pub tick_interval: Option<f32>,
+    pub map_name: String,
+    pub map_crc: u32,
+    pub max_clients: i32,
+    pub max_classes: i32,
+    pub is_hltv: bool,
+    pub host_name: String,
+}
+
+impl ServerInfo {
+    pub fn new(msg: &CsvcMsgServerInfo) -> ServerInfo {
+        ServerInfo {
+            tick_interval: msg.tick_interval.filter(|interval| *interval > 0.0),
+            map_name: msg.map_name.clone().unwrap_or_default(),
+            map_crc: msg.map_crc.unwrap_or(0),
+            max_clients: msg.max_clients.unwrap_or(0),
+            max_classes: msg.max_classes.unwrap_or(0),
+            is_hltv: msg.is_hltv.unwrap_or(false),
+            host_name: msg.host_name.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Ticks per second, if the tick interval is known.
+    pub fn tickrate(&self) -> Option<i32> {
+        self.tick_interval
+            .map(|interval| (1.0 / interval).round() as i32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::serverinfo::*;
+
+    #[test]
+    fn server_info() {
+        let msg = CsvcMsgServerInfo {
+            tick_interval: Some(0.0078125),
+            map_name: Some("de_inferno".to_string()),
+            map_crc: Some(0xDEADBEEF),
+            max_clients: Some(12),
+            max_classes: Some(283),
+            is_hltv: Some(true),
+            ..Default::default()
+        };
+        let info = ServerInfo::new(&msg);
+        assert_eq!(Some(128), info.tickrate());
+        assert_eq!("de_inferno", info.map_name);
+        assert_eq!(0xDEADBEEF, info.map_crc);
+        assert_eq!(12, info.max_clients);
+        assert_eq!(283, info.max_classes);
+        assert!(info.is_hltv);
+
+        // Intervals are rounded to the nearest tickrate
+        let msg = CsvcMsgServerInfo {
+            tick_interval: Some(0.015625001),
+            ..Default::default()
+        };
+        assert_eq!(Some(64), ServerInfo::new(&msg).tickrate());
+
+        // A missing or zero interval leaves the tickrate unknown
+        let info = ServerInfo::new(&CsvcMsgServerInfo {
+            tick_interval: Some(0.0),
+            map_name: Some("de_nuke".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(None, info.tickrate());
+        assert_eq!("de_nuke", info.map_name);
+        assert_eq!(
+            None,
+            ServerInfo::new(&CsvcMsgServerInfo::default()).tickrate()
+        );
+    }
+}
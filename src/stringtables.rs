@@ -1,95 +1,130 @@
 use std::collections::{HashMap, VecDeque};
-use std::convert::TryInto;
 use std::io::Read;
 
+use log::warn;
+
 use crate::bitreader::{BitReader, ReadExtras};
 use crate::csgo::netmessages_public;
-use crate::playerinfo::PlayerInfo;
+use crate::error::{DemoError, Result};
+
+#[derive(Clone, Debug, Default)]
+pub struct StringTableEntry {
+    pub string: String,
+    pub userdata: Option<Vec<u8>>,
+}
 
-#[derive(Debug, Clone, Copy)]
+/// One string table with all of its current entries.
+#[derive(Debug, Clone)]
 pub struct StringTable {
+    pub name: String,
     max_entries: i32,
     entry_index: i64,
     user_data_fixed_size: bool,
     user_data_size_bits: Option<i32>,
+    entries: HashMap<i64, StringTableEntry>,
 }
 
+/// Decode a string table update, returning the changed entries by index.
+/// Userdata is only set for entries whose userdata changed.
+///
+/// Updates from servers with `stringtable_usedictionaries` enabled start with
+/// a set bit and refer to strings in dictionaries that are not part of the
+/// demo. Valve's own demoinfogo gives up on them too ("Encoded with
+/// dictionaries, unable to decode"), so they fail with
+/// [`DemoError::Unsupported`].
 fn calculate_string_table(
     table: &mut StringTable,
     table_entries: i32,
     data: &[u8],
-) -> std::io::Result<HashMap<i32, PlayerInfo>> {
-    let mut players = HashMap::new();
-
-    if table.user_data_fixed_size {
-        assert!(table.user_data_size_bits.is_some());
-    }
+) -> Result<Vec<(i64, StringTableEntry)>> {
+    let mut changes = Vec::new();
 
     let reader_buf = &mut &*data;
     let mut reader = BitReader::new(reader_buf);
 
     let entry_bits = (table.max_entries as f64).log2().ceil() as usize;
-    let mut entries: HashMap<i64, (Vec<u8>, Vec<u8>)> = HashMap::new();
     let mut history: VecDeque<Vec<u8>> = VecDeque::new();
 
-    assert!(!reader.read_bit()?, "Dictionary encoding unsupported");
+    if reader.read_bit()? {
+        return Err(DemoError::Unsupported(format!(
+            "{} is dictionary encoded",
+            table.name
+        )));
+    }
 
+    table.entry_index = -1;
     for _i in 0..table_entries {
         table.entry_index += 1;
         if !reader.read_bit()? {
             table.entry_index = reader.read_bits_u32(entry_bits as u8)? as i64;
         }
 
-        assert!(table.entry_index >= 0 && table.entry_index < (table.max_entries as i64));
+        if table.entry_index < 0 || table.entry_index >= (table.max_entries as i64) {
+            return Err(DemoError::StringTableCorruption(format!(
+                "entry index {} out of bounds (max {})",
+                table.entry_index, table.max_entries
+            )));
+        }
 
-        let entry: Vec<u8>;
-        let mut userdata: Vec<u8> = Vec::new();
+        let mut entry: Vec<u8> = Vec::new();
+        let mut userdata = None;
 
         if reader.read_bit()? {
             // substring check
             if reader.read_bit()? {
                 let index = reader.read_bits_u32(5)? as usize;
-                assert!(index < history.len(), "History index too large");
                 let bytes_to_copy = reader.read_bits_u32(5)? as usize;
 
-                let last = &history[index];
-
-                let substr = last.split_at(bytes_to_copy).0;
+                let last = history.get(index).ok_or_else(|| {
+                    DemoError::StringTableCorruption(format!("history index {} too large", index))
+                })?;
+                let substr = last.get(..bytes_to_copy).ok_or_else(|| {
+                    DemoError::StringTableCorruption(format!(
+                        "substring length {} too large",
+                        bytes_to_copy
+                    ))
+                })?;
                 let suffix = reader.read_c_string()?;
 
                 entry = substr.iter().chain(suffix.iter()).copied().collect();
             } else {
                 entry = reader.read_c_string()?;
             }
-        } else {
+        } else if let Some(old) = table.entries.get(&table.entry_index) {
             // If the string itself hasn't changed, this entry must already exist
-            let tuple = entries
-                .get(&table.entry_index)
-                .cloned()
-                .unwrap_or((vec![], vec![]));
-            entry = tuple.0;
-            userdata = tuple.1;
+            entry = old.string.clone().into_bytes();
         }
 
         if reader.read_bit()? {
             // don't read the length, it's fixed length and the length was networked down already
-            if table.user_data_fixed_size {
-                userdata = vec![reader
-                    .read_bits_u32(table.user_data_size_bits.unwrap() as u8)?
-                    .try_into()
-                    .unwrap()];
+            if let Some(bits) = table
+                .user_data_size_bits
+                .filter(|_| table.user_data_fixed_size)
+            {
+                // the bits are stored little endian, the last byte may be partial
+                let mut buf = Vec::with_capacity((bits as usize).div_ceil(8));
+                let mut remaining = bits;
+                while remaining > 0 {
+                    let count = remaining.min(8);
+                    buf.push(reader.read_bits_u32(count as u8)? as u8);
+                    remaining -= count;
+                }
+                userdata = Some(buf);
             } else {
                 let bytes = reader.read_bits_u32(14)? as usize;
                 let mut buf = vec![0; bytes];
                 reader.read_exact(&mut buf)?;
-                userdata = buf;
+                userdata = Some(buf);
             }
-
-            let info = PlayerInfo::new(table.entry_index, &userdata)?;
-            players.insert(info.user_id, info);
         }
 
-        entries.insert(table.entry_index, (entry.clone(), userdata));
+        let string = String::from_utf8_lossy(&entry).to_string();
+        let stored = table.entries.entry(table.entry_index).or_default();
+        stored.string = string.clone();
+        if userdata.is_some() {
+            stored.userdata = userdata.clone();
+        }
+        changes.push((table.entry_index, StringTableEntry { string, userdata }));
 
         // add to history
         if history.len() > 31 {
@@ -99,42 +134,409 @@ fn calculate_string_table(
         history.push_back(entry);
     }
 
-    Ok(players)
+    Ok(changes)
 }
 
-pub fn create_string_table(
-    msg: netmessages_public::CsvcMsgCreateStringTable,
-) -> std::io::Result<Option<(StringTable, HashMap<i32, PlayerInfo>)>> {
-    let name = msg.name.unwrap();
-    //println!("Stringtables: {}", name);
-    if name != "userinfo" {
-        return Ok(None);
+/// Like [`calculate_string_table`], but an update we can't decode is skipped
+/// with a warning so the rest of the demo can still be parsed.
+fn apply_string_table(
+    table: &mut StringTable,
+    table_entries: i32,
+    data: &[u8],
+) -> Result<Vec<(i64, StringTableEntry)>> {
+    match calculate_string_table(table, table_entries, data) {
+        Err(DemoError::Unsupported(msg)) => {
+            warn!("Skipping string table update: {}", msg);
+            Ok(Vec::new())
+        }
+        result => result,
+    }
+}
+
+impl StringTable {
+    pub fn max_entries(&self) -> i32 {
+        self.max_entries
+    }
+
+    pub fn entry(&self, index: i64) -> Option<&StringTableEntry> {
+        self.entries.get(&index)
+    }
+
+    /// Userdata of an entry, if it has any.
+    pub fn userdata(&self, index: i64) -> Option<&[u8]> {
+        self.entry(index)
+            .and_then(|entry| entry.userdata.as_deref())
     }
 
+    /// Entries sorted by index.
+    pub fn entries(&self) -> Vec<(i64, &StringTableEntry)> {
+        let mut entries = self
+            .entries
+            .iter()
+            .map(|(i, entry)| (*i, entry))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(i, _)| *i);
+        entries
+    }
+
+    /// Find an entry by its string.
+    pub fn find(&self, string: &str) -> Option<(i64, &StringTableEntry)> {
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.string == string)
+            .map(|(i, entry)| (*i, entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn create_string_table(
+    msg: netmessages_public::CsvcMsgCreateStringTable,
+) -> Result<(StringTable, Vec<(i64, StringTableEntry)>)> {
+    let name = msg.name.ok_or(DemoError::MissingField("name"))?;
+
     let mut table = StringTable {
-        max_entries: msg.max_entries.unwrap(),
+        name,
+        max_entries: msg
+            .max_entries
+            .ok_or(DemoError::MissingField("max_entries"))?,
         entry_index: -1,
         user_data_fixed_size: msg.user_data_fixed_size.filter(|n| *n).is_some(),
         user_data_size_bits: msg.user_data_size_bits.filter(|n| *n != 0),
+        entries: HashMap::new(),
     };
 
-    if table.user_data_fixed_size {
-        assert!(table.user_data_size_bits.is_some());
+    if table.user_data_fixed_size && table.user_data_size_bits.is_none() {
+        return Err(DemoError::StringTableCorruption(
+            "fixed size userdata without a size".to_string(),
+        ));
     }
 
-    let _flags = msg.flags.unwrap();
-    let string_data: &[u8] = &msg.string_data.unwrap();
+    let string_data: &[u8] = &msg
+        .string_data
+        .ok_or(DemoError::MissingField("string_data"))?;
+    let num_entries = msg
+        .num_entries
+        .ok_or(DemoError::MissingField("num_entries"))?;
 
-    let players = calculate_string_table(&mut table, msg.num_entries.unwrap(), string_data)?;
+    let changes = apply_string_table(&mut table, num_entries, string_data)?;
 
-    Ok(Some((table, players)))
+    Ok((table, changes))
 }
 
-pub fn update_string_table(
+fn update_string_table(
     table: &mut StringTable,
     msg: netmessages_public::CsvcMsgUpdateStringTable,
-) -> std::io::Result<HashMap<i32, PlayerInfo>> {
-    let string_data: &[u8] = &msg.string_data.unwrap();
+) -> Result<Vec<(i64, StringTableEntry)>> {
+    let string_data: &[u8] = &msg
+        .string_data
+        .ok_or(DemoError::MissingField("string_data"))?;
+    let num_changed_entries = msg
+        .num_changed_entries
+        .ok_or(DemoError::MissingField("num_changed_entries"))?;
+
+    apply_string_table(table, num_changed_entries, string_data)
+}
+
+/// Every string table created so far, by id and by name.
+///
+/// Table ids are assigned in creation order, which is how
+/// `svc_UpdateStringTable` refers to them.
+#[derive(Clone, Debug, Default)]
+pub struct StringTables {
+    tables: Vec<StringTable>,
+    ids: HashMap<String, usize>,
+}
+
+impl StringTables {
+    /// Create a table from `svc_CreateStringTable`, returning it and its initial entries.
+    pub fn create(
+        &mut self,
+        msg: netmessages_public::CsvcMsgCreateStringTable,
+    ) -> Result<(&StringTable, Vec<(i64, StringTableEntry)>)> {
+        let (table, changes) = create_string_table(msg)?;
+        self.ids.insert(table.name.clone(), self.tables.len());
+        self.tables.push(table);
+        Ok((self.tables.last().unwrap(), changes))
+    }
+
+    /// Apply `svc_UpdateStringTable` to the table it refers to, returning the changed entries.
+    pub fn update(
+        &mut self,
+        msg: netmessages_public::CsvcMsgUpdateStringTable,
+    ) -> Result<(&StringTable, Vec<(i64, StringTableEntry)>)> {
+        let id = msg.table_id.ok_or(DemoError::MissingField("table_id"))?;
+        let table = self.tables.get_mut(id as usize).ok_or_else(|| {
+            DemoError::StringTableCorruption(format!("update for unknown table {}", id))
+        })?;
+        let changes = update_string_table(table, msg)?;
+        Ok((table, changes))
+    }
+
+    /// Replace the entries of known tables with the contents of a `dem_stringtables` frame.
+    pub fn apply_snapshot(&mut self, snapshot: &[StringTableSnapshot]) {
+        for table in snapshot {
+            if let Some(i) = self.ids.get(&table.name) {
+                self.tables[*i].entries = table
+                    .entries
+                    .iter()
+                    .cloned()
+                    .enumerate()
+                    .map(|(i, entry)| (i as i64, entry))
+                    .collect();
+            }
+        }
+    }
 
-    calculate_string_table(table, msg.num_changed_entries.unwrap(), string_data)
+    pub fn get(&self, id: i32) -> Option<&StringTable> {
+        self.tables.get(id as usize)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&StringTable> {
+        self.ids.get(name).map(|i| &self.tables[*i])
+    }
+
+    /// Tables in id order.
+    pub fn iter(&self) -> impl Iterator<Item = &StringTable> {
+        self.tables.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+/// Full contents of one table in a `dem_stringtables` frame.
+#[derive(Clone, Debug, Default)]
+pub struct StringTableSnapshot {
+    pub name: String,
+    pub entries: Vec<StringTableEntry>,
+    pub client_entries: Vec<StringTableEntry>,
+}
+
+fn read_snapshot_entries<R: Read>(reader: &mut BitReader<R>) -> Result<Vec<StringTableEntry>> {
+    let count = reader.read_u16()?;
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let string = String::from_utf8_lossy(&reader.read_c_string()?).to_string();
+        let userdata = if reader.read_bit()? {
+            let size = reader.read_u16()?;
+            Some(reader.read_u8_vec(size as usize)?)
+        } else {
+            None
+        };
+        entries.push(StringTableEntry { string, userdata });
+    }
+    Ok(entries)
+}
+
+/// Parse the payload of a `dem_stringtables` frame.
+pub fn read_string_tables_snapshot(data: &[u8]) -> Result<Vec<StringTableSnapshot>> {
+    let mut reader = BitReader::new(data);
+
+    let table_count = reader.read_u8()?;
+    let mut tables = Vec::with_capacity(table_count as usize);
+    for _ in 0..table_count {
+        let name = String::from_utf8_lossy(&reader.read_c_string()?).to_string();
+        let entries = read_snapshot_entries(&mut reader)?;
+        let client_entries = if reader.read_bit()? {
+            read_snapshot_entries(&mut reader)?
+        } else {
+            Vec::new()
+        };
+        tables.push(StringTableSnapshot {
+            name,
+            entries,
+            client_entries,
+        });
+    }
+
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitreader::BitWriter;
+    use crate::stringtables::*;
+
+    fn create_msg(
+        name: &str,
+        max_entries: i32,
+        num_entries: i32,
+        string_data: Vec<u8>,
+    ) -> netmessages_public::CsvcMsgCreateStringTable {
+        netmessages_public::CsvcMsgCreateStringTable {
+            name: Some(name.to_string()),
+            max_entries: Some(max_entries),
+            num_entries: Some(num_entries),
+            user_data_fixed_size: Some(false),
+            user_data_size: Some(0),
+            user_data_size_bits: Some(0),
+            flags: Some(0),
+            string_data: Some(string_data),
+        }
+    }
+
+    /// Write an entry at the next index with a new string and optional userdata.
+    fn write_entry<'a>(w: &'a mut BitWriter, string: &str, userdata: &[u8]) -> &'a mut BitWriter {
+        w.write_bit(true).write_bit(true).write_bit(false);
+        w.write_bytes(string.as_bytes()).write_bytes(&[0]);
+        w.write_bit(!userdata.is_empty());
+        if !userdata.is_empty() {
+            w.write_bits(userdata.len() as u32, 14)
+                .write_bytes(userdata);
+        }
+        w
+    }
+
+    #[test]
+    fn string_tables() {
+        let mut tables = StringTables::default();
+
+        let mut w = BitWriter::new();
+        w.write_bit(false);
+        write_entry(&mut w, "models/a.mdl", &[]);
+        write_entry(&mut w, "models/b.mdl", &[]);
+        let (table, changes) = tables
+            .create(create_msg("modelprecache", 8, 2, w.finish()))
+            .unwrap();
+        assert_eq!("modelprecache", table.name);
+        assert_eq!(2, changes.len());
+
+        let mut w = BitWriter::new();
+        w.write_bit(false);
+        write_entry(&mut w, "40", &[1, 2, 3]);
+        tables
+            .create(create_msg("instancebaseline", 8, 1, w.finish()))
+            .unwrap();
+
+        // Change the userdata of entry 0 in table 1 without resending its string
+        let mut w = BitWriter::new();
+        w.write_bit(false).write_bit(true).write_bit(false);
+        w.write_bit(true).write_bits(1, 14).write_bytes(&[9]);
+        let msg = netmessages_public::CsvcMsgUpdateStringTable {
+            table_id: Some(1),
+            num_changed_entries: Some(1),
+            string_data: Some(w.finish()),
+        };
+        let (table, changes) = tables.update(msg).unwrap();
+        assert_eq!("instancebaseline", table.name);
+        assert_eq!("40", changes[0].1.string);
+
+        assert_eq!(2, tables.len());
+        let models = tables.by_name("modelprecache").unwrap();
+        assert_eq!(
+            vec!["models/a.mdl", "models/b.mdl"],
+            models
+                .entries()
+                .iter()
+                .map(|(_, e)| e.string.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(1), models.find("models/b.mdl").map(|(i, _)| i));
+        assert_eq!(Some(&[9][..]), tables.get(1).unwrap().userdata(0));
+        assert!(tables.by_name("soundprecache").is_none());
+
+        let msg = netmessages_public::CsvcMsgUpdateStringTable {
+            table_id: Some(5),
+            num_changed_entries: Some(0),
+            string_data: Some(vec![0]),
+        };
+        assert!(matches!(
+            tables.update(msg),
+            Err(DemoError::StringTableCorruption(_))
+        ));
+    }
+
+    #[test]
+    fn fixed_size_userdata() {
+        let fixed = |bits: i32, value: u32| {
+            let mut w = BitWriter::new();
+            w.write_bit(false);
+            w.write_bit(true).write_bit(true).write_bit(false);
+            w.write_bytes(b"0\0");
+            w.write_bit(true).write_bits(value, bits as u8);
+            let msg = netmessages_public::CsvcMsgCreateStringTable {
+                user_data_fixed_size: Some(true),
+                user_data_size: Some((bits as u32).div_ceil(8) as i32),
+                user_data_size_bits: Some(bits),
+                ..create_msg("fixed", 8, 1, w.finish())
+            };
+            let mut tables = StringTables::default();
+            let (table, changes) = tables.create(msg).unwrap();
+            assert_eq!(changes[0].1.userdata.as_deref(), table.userdata(0));
+            changes[0].1.userdata.clone().unwrap()
+        };
+        assert_eq!(vec![0xBC, 0x0A], fixed(12, 0xABC));
+        assert_eq!(vec![0xEF, 0xBE], fixed(16, 0xBEEF));
+        assert_eq!(vec![0x05], fixed(3, 0x5));
+    }
+
+    #[test]
+    fn dictionary_encoding_is_skipped() {
+        let mut w = BitWriter::new();
+        w.write_bit(true);
+        write_entry(&mut w, "models/a.mdl", &[]);
+        let data = w.finish();
+
+        let mut table = create_string_table(create_msg("modelprecache", 8, 0, vec![0]))
+            .unwrap()
+            .0;
+        let err = calculate_string_table(&mut table, 1, &data).unwrap_err();
+        assert!(matches!(err, DemoError::Unsupported(msg) if msg.contains("modelprecache")));
+
+        // The table is still created, and later plain updates apply to it
+        let mut tables = StringTables::default();
+        let (table, changes) = tables
+            .create(create_msg("modelprecache", 8, 1, data.clone()))
+            .unwrap();
+        assert!(table.is_empty());
+        assert!(changes.is_empty());
+        let msg = |string_data| netmessages_public::CsvcMsgUpdateStringTable {
+            table_id: Some(0),
+            num_changed_entries: Some(1),
+            string_data: Some(string_data),
+        };
+        assert!(tables.update(msg(data)).unwrap().1.is_empty());
+        let mut w = BitWriter::new();
+        w.write_bit(false);
+        write_entry(&mut w, "models/b.mdl", &[]);
+        let (table, _) = tables.update(msg(w.finish())).unwrap();
+        assert_eq!("models/b.mdl", table.entry(0).unwrap().string);
+    }
+
+    #[test]
+    fn string_tables_snapshot() {
+        let data = BitWriter::new()
+            .write_bytes(&[1])
+            .write_bytes(b"modelprecache\0")
+            .write_bytes(&[2, 0])
+            .write_bytes(b"a\0")
+            .write_bit(false)
+            .write_bytes(b"b\0")
+            .write_bit(true)
+            .write_bytes(&[2, 0, 0xAB, 0xCD])
+            .write_bit(false)
+            .finish();
+
+        let tables = read_string_tables_snapshot(&data).unwrap();
+        assert_eq!(1, tables.len());
+        assert_eq!("modelprecache", tables[0].name);
+        assert_eq!(2, tables[0].entries.len());
+        assert_eq!("a", tables[0].entries[0].string);
+        assert_eq!(None, tables[0].entries[0].userdata);
+        assert_eq!("b", tables[0].entries[1].string);
+        assert_eq!(Some(vec![0xAB, 0xCD]), tables[0].entries[1].userdata);
+        assert!(tables[0].client_entries.is_empty());
+    }
 }
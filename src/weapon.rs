@@ -0,0 +1,216 @@
+use crate::grenade::GrenadeType;
+
+/// Kind of weapon, for grouping weapon stats.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WeaponCategory {
+    Pistol,
+    Smg,
+    Rifle,
+    Sniper,
+    Shotgun,
+    Heavy,
+    Grenade,
+    Knife,
+    /// Armor and defuse kits.
+    Equipment,
+    /// Zeus, C4 and damage from the world, like falling.
+    Other,
+}
+
+impl WeaponCategory {
+    /// Whether shots with weapons of the category count towards accuracy.
+    pub fn is_gun(self) -> bool {
+        !matches!(
+            self,
+            WeaponCategory::Grenade
+                | WeaponCategory::Knife
+                | WeaponCategory::Equipment
+                | WeaponCategory::Other
+        )
+    }
+}
+
+/// A weapon, normalized from the names used in game events.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weapon {
+    Glock,
+    P2000,
+    UspS,
+    P250,
+    FiveSeven,
+    Tec9,
+    Cz75,
+    DualBerettas,
+    Deagle,
+    Revolver,
+    Mac10,
+    Mp9,
+    Mp7,
+    Mp5,
+    Ump45,
+    P90,
+    Bizon,
+    Ak47,
+    M4a4,
+    M4a1S,
+    Galil,
+    Famas,
+    Sg553,
+    Aug,
+    Awp,
+    Ssg08,
+    G3sg1,
+    Scar20,
+    Nova,
+    Xm1014,
+    SawedOff,
+    Mag7,
+    M249,
+    Negev,
+    HeGrenade,
+    Flashbang,
+    Smoke,
+    Molotov,
+    Incendiary,
+    Decoy,
+    /// Fire from a molotov or incendiary, in `player_hurt` and `player_death`.
+    Inferno,
+    Knife,
+    Zeus,
+    C4,
+    Kevlar,
+    /// Kevlar and helmet.
+    KevlarHelmet,
+    DefuseKit,
+    World,
+    Unknown(String),
+}
+
+impl Default for Weapon {
+    fn default() -> Weapon {
+        Weapon::Unknown(String::new())
+    }
+}
+
+impl Weapon {
+    /// Weapon of an event name like `ak47`, with or without the `weapon_` or
+    /// `item_` prefix.
+    pub fn from_name(name: &str) -> Weapon {
+        let name = name
+            .trim_start_matches("weapon_")
+            .trim_start_matches("item_");
+        match name {
+            "glock" => Weapon::Glock,
+            "hkp2000" => Weapon::P2000,
+            "usp_silencer" | "usp_silencer_off" => Weapon::UspS,
+            "p250" => Weapon::P250,
+            "fiveseven" => Weapon::FiveSeven,
+            "tec9" => Weapon::Tec9,
+            "cz75a" => Weapon::Cz75,
+            "elite" => Weapon::DualBerettas,
+            "deagle" => Weapon::Deagle,
+            "revolver" => Weapon::Revolver,
+            "mac10" => Weapon::Mac10,
+            "mp9" => Weapon::Mp9,
+            "mp7" => Weapon::Mp7,
+            "mp5sd" => Weapon::Mp5,
+            "ump45" => Weapon::Ump45,
+            "p90" => Weapon::P90,
+            "bizon" => Weapon::Bizon,
+            "ak47" => Weapon::Ak47,
+            "m4a1" => Weapon::M4a4,
+            "m4a1_silencer" | "m4a1_silencer_off" => Weapon::M4a1S,
+            "galilar" => Weapon::Galil,
+            "famas" => Weapon::Famas,
+            "sg556" => Weapon::Sg553,
+            "aug" => Weapon::Aug,
+            "awp" => Weapon::Awp,
+            "ssg08" => Weapon::Ssg08,
+            "g3sg1" => Weapon::G3sg1,
+            "scar20" => Weapon::Scar20,
+            "nova" => Weapon::Nova,
+            "xm1014" => Weapon::Xm1014,
+            "sawedoff" => Weapon::SawedOff,
+            "mag7" => Weapon::Mag7,
+            "m249" => Weapon::M249,
+            "negev" => Weapon::Negev,
+            "hegrenade" => Weapon::HeGrenade,
+            "flashbang" => Weapon::Flashbang,
+            "smokegrenade" => Weapon::Smoke,
+            "molotov" => Weapon::Molotov,
+            "incgrenade" => Weapon::Incendiary,
+            "decoy" => Weapon::Decoy,
+            "inferno" => Weapon::Inferno,
+            "taser" => Weapon::Zeus,
+            "c4" | "planted_c4" => Weapon::C4,
+            "kevlar" | "vest" => Weapon::Kevlar,
+            "assaultsuit" | "vesthelm" => Weapon::KevlarHelmet,
+            "defuser" | "cutters" => Weapon::DefuseKit,
+            "world" => Weapon::World,
+            name if name.starts_with("knife") || name == "bayonet" => Weapon::Knife,
+            name => Weapon::Unknown(name.to_string()),
+        }
+    }
+
+    pub fn category(&self) -> WeaponCategory {
+        use Weapon::*;
+        match self {
+            Glock | P2000 | UspS | P250 | FiveSeven | Tec9 | Cz75 | DualBerettas | Deagle
+            | Revolver => WeaponCategory::Pistol,
+            Mac10 | Mp9 | Mp7 | Mp5 | Ump45 | P90 | Bizon => WeaponCategory::Smg,
+            Ak47 | M4a4 | M4a1S | Galil | Famas | Sg553 | Aug => WeaponCategory::Rifle,
+            Awp | Ssg08 | G3sg1 | Scar20 => WeaponCategory::Sniper,
+            Nova | Xm1014 | SawedOff | Mag7 => WeaponCategory::Shotgun,
+            M249 | Negev => WeaponCategory::Heavy,
+            HeGrenade | Flashbang | Smoke | Molotov | Incendiary | Decoy | Inferno => {
+                WeaponCategory::Grenade
+            }
+            Knife => WeaponCategory::Knife,
+            Kevlar | KevlarHelmet | DefuseKit => WeaponCategory::Equipment,
+            Zeus | C4 | World | Unknown(_) => WeaponCategory::Other,
+        }
+    }
+
+    /// Grenade type of a thrown grenade. Fire is not a grenade of its own.
+    pub fn grenade_type(&self) -> Option<GrenadeType> {
+        match self {
+            Weapon::HeGrenade => Some(GrenadeType::HighExplosive),
+            Weapon::Flashbang => Some(GrenadeType::Flashbang),
+            Weapon::Smoke => Some(GrenadeType::Smoke),
+            Weapon::Molotov => Some(GrenadeType::Molotov),
+            Weapon::Incendiary => Some(GrenadeType::Incendiary),
+            Weapon::Decoy => Some(GrenadeType::Decoy),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::weapon::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(Weapon::Ak47, Weapon::from_name("weapon_ak47"));
+        assert_eq!(Weapon::M4a1S, Weapon::from_name("m4a1_silencer_off"));
+        assert_eq!(Weapon::Knife, Weapon::from_name("knife_karambit"));
+        assert_eq!(Weapon::Knife, Weapon::from_name("weapon_bayonet"));
+        assert_eq!(
+            Weapon::Unknown("shield".to_string()),
+            Weapon::from_name("weapon_shield")
+        );
+
+        assert_eq!(WeaponCategory::Sniper, Weapon::Awp.category());
+        assert_eq!(
+            WeaponCategory::Pistol,
+            Weapon::from_name("cz75a").category()
+        );
+        assert_eq!(WeaponCategory::Grenade, Weapon::Inferno.category());
+        assert_eq!(Weapon::KevlarHelmet, Weapon::from_name("item_assaultsuit"));
+        assert!(!Weapon::DefuseKit.category().is_gun());
+        assert!(WeaponCategory::Heavy.is_gun());
+        assert!(!Weapon::Zeus.category().is_gun());
+        assert_eq!(None, Weapon::Inferno.grenade_type());
+        assert_eq!(Some(GrenadeType::Smoke), Weapon::Smoke.grenade_type());
+    }
+}